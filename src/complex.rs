@@ -1,10 +1,53 @@
-use core::f32::consts::PI;
+use core::cmp::Ordering;
+use core::f32::consts::{FRAC_2_SQRT_PI, LN_10, LN_2, PI};
 use core::fmt::Display;
 use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-use libm::{acosf, asinf, atan2f, atanf, cosf, expf, fabsf, logf, sinf, sqrtf, tanf};
-
-#[derive(Clone)]
+use libm::{
+    acosf, asinf, atan2f, atanf, cbrtf, ceilf, copysignf, cosf, expf, fabsf, floorf, log10f, logf,
+    powf, roundf, sinf, sinhf, sqrtf, tanf, tgammaf,
+};
+
+// Lanczos approximation parameters for g = 7, in single precision gamma is accurate to ~6 digits
+const LANCZOS_G: f32 = 7.;
+const LANCZOS_COEFFICIENTS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+const SQRT_TAU: f32 = 2.506_628_3;
+
+// Partial sums used by the zeta function, more terms are slower but more precise
+pub const ZETA_TERMS: u32 = 20;
+// B_2k / (2k)! for the Euler-Maclaurin corrections of zeta
+const ZETA_CORRECTIONS: [f32; 6] = [
+    1. / 12.,
+    -1. / 720.,
+    1. / 30_240.,
+    -1. / 1_209_600.,
+    1. / 47_900_160.,
+    -691. / 1_307_674_368_000.,
+];
+
+// Abramowitz & Stegun 7.1.26 for erf on the real line, accurate to 1.5e-7
+const ERF_P: f32 = 0.327_591_1;
+const ERF_COEFFICIENTS: [f32; 5] = [
+    0.254_829_6,
+    -0.284_496_74,
+    1.421_413_8,
+    -1.453_152,
+    1.061_405_4,
+];
+// Terms of the Taylor series of erf elsewhere, it drifts away once |z| goes past 2.5
+pub const ERF_TERMS: u32 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ComplexRect {
     pub from_real: f32,
     pub to_real: f32,
@@ -12,7 +55,121 @@ pub struct ComplexRect {
     pub to_imag: f32,
 }
 
-#[derive(Debug, Clone, Copy)]
+// Narrower areas are lost in f32 rounding
+pub const MIN_RECT_EXTENT: f32 = 1e-6;
+
+impl ComplexRect {
+    // [-1, 1] × [-1, 1], a reference area for tests
+    #[cfg(test)]
+    pub fn unit() -> Self {
+        ComplexRect::from_center(Complex::ZERO, 1., 1.)
+    }
+
+    // [-10, 10] × [-10, 10]
+    pub fn standard() -> Self {
+        ComplexRect::from_center(Complex::ZERO, 10., 10.)
+    }
+
+    pub fn from_center(c: Complex, half_width: f32, half_height: f32) -> Self {
+        ComplexRect {
+            from_real: c.real - half_width,
+            to_real: c.real + half_width,
+            from_imag: c.imag - half_height,
+            to_imag: c.imag + half_height,
+        }
+    }
+
+    pub fn center(&self) -> Complex {
+        Complex {
+            real: (self.from_real + self.to_real) / 2.,
+            imag: (self.from_imag + self.to_imag) / 2.,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let from = Complex {
+            real: self.from_real,
+            imag: self.from_imag,
+        };
+        let to = Complex {
+            real: self.to_real,
+            imag: self.to_imag,
+        };
+        from.is_finite() && to.is_finite() && from.real < to.real && from.imag < to.imag
+    }
+
+    pub fn width(&self) -> f32 {
+        self.to_real - self.from_real
+    }
+
+    pub fn height(&self) -> f32 {
+        self.to_imag - self.from_imag
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width() / self.height()
+    }
+
+    // Position of z on a width × height screen showing the area, the imaginary axis going up
+    pub fn to_screen(self, z: Complex, width: u16, height: u16) -> (f32, f32) {
+        (
+            (z.real - self.from_real) / self.width() * width as f32,
+            (1. - (z.imag - self.from_imag) / self.height()) * height as f32,
+        )
+    }
+
+    pub fn pan(&self, delta: Complex) -> ComplexRect {
+        ComplexRect {
+            from_real: self.from_real + delta.real,
+            to_real: self.to_real + delta.real,
+            from_imag: self.from_imag + delta.imag,
+            to_imag: self.to_imag + delta.imag,
+        }
+    }
+
+    // Corners are interpolated separately, t = 0 gives self and t = 1 gives other
+    pub fn lerp(&self, other: &ComplexRect, t: f32) -> ComplexRect {
+        let from = Complex::from((self.from_real, self.from_imag))
+            .lerp(Complex::from((other.from_real, other.from_imag)), t);
+        let to = Complex::from((self.to_real, self.to_imag))
+            .lerp(Complex::from((other.to_real, other.to_imag)), t);
+
+        ComplexRect {
+            from_real: from.real,
+            to_real: to.real,
+            from_imag: from.imag,
+            to_imag: to.imag,
+        }
+    }
+
+    // Each bound rounded to the nearest multiple of step
+    pub fn snap_to_grid(&self, step: f32) -> ComplexRect {
+        let snap = |x: f32| roundf(x / step) * step;
+        ComplexRect {
+            from_real: snap(self.from_real),
+            to_real: snap(self.to_real),
+            from_imag: snap(self.from_imag),
+            to_imag: snap(self.to_imag),
+        }
+    }
+
+    // The power of ten fitting about four times in the width, 1 for a width of 20
+    pub fn grid_step(&self) -> f32 {
+        powf(10., floorf(log10f(self.width() / 4.)))
+    }
+
+    // The steps areas from self to other, ending on other
+    pub fn viewport_lerp_sequence(
+        &self,
+        other: &ComplexRect,
+        steps: u16,
+    ) -> impl Iterator<Item = ComplexRect> {
+        let (start, end) = (*self, *other);
+        (1..=steps).map(move |i| start.lerp(&end, i as f32 / steps as f32))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Complex {
     pub real: f32,
     pub imag: f32,
@@ -25,20 +182,45 @@ impl Display for Complex {
 }
 
 impl Complex {
-    pub const ZERO: Complex = Complex { real: 0., imag: 0. };
-    pub const I: Complex = Complex { real: 0., imag: 1. };
+    pub const ZERO: Complex = Complex::from_real(0.);
+    pub const I: Complex = Complex::from_imag(1.);
+    pub const ONE: Complex = Complex::from_real(1.);
+    pub const NEG_ONE: Complex = Complex::from_real(-1.);
+    pub const NEG_I: Complex = Complex::from_imag(-1.);
 
-    pub fn from_real(real: f32) -> Self {
+    pub const fn from_real(real: f32) -> Self {
         Complex { real, imag: 0. }
     }
-    pub fn from_imag(imag: f32) -> Self {
+    pub const fn from_imag(imag: f32) -> Self {
         Complex { real: 0., imag }
     }
+    pub fn lerp(self, other: Complex, t: f32) -> Complex {
+        self * (1. - t) + other * t
+    }
+
+    pub fn from_polar(modulus: f32, argument: f32) -> Self {
+        Complex {
+            real: modulus * cosf(argument),
+            imag: modulus * sinf(argument),
+        }
+    }
+
+    pub fn cis(theta: f32) -> Self {
+        Complex {
+            real: cosf(theta),
+            imag: sinf(theta),
+        }
+    }
 
     pub fn squared_modulus(self) -> f32 {
         self.real * self.real + self.imag * self.imag
     }
 
+    // Shorter name for squared_modulus, |z|² without the square root
+    pub fn norm_sq(self) -> f32 {
+        self.squared_modulus()
+    }
+
     pub fn modulus(self) -> f32 {
         sqrtf(self.squared_modulus())
     }
@@ -54,6 +236,189 @@ impl Complex {
     pub fn is_real(self) -> bool {
         self.imag == 0.
     }
+
+    pub fn is_finite(self) -> bool {
+        self.real.is_finite() && self.imag.is_finite()
+    }
+
+    // Undefined results such as 0/0, either part being NaN is enough
+    pub fn is_nan(self) -> bool {
+        self.real.is_nan() || self.imag.is_nan()
+    }
+
+    // True for both signed zeros
+    pub fn is_zero(self) -> bool {
+        self.real == 0. && self.imag == 0.
+    }
+
+    pub fn floor(self) -> Complex {
+        Complex {
+            real: floorf(self.real),
+            imag: floorf(self.imag),
+        }
+    }
+
+    pub fn ceil(self) -> Complex {
+        Complex {
+            real: ceilf(self.real),
+            imag: ceilf(self.imag),
+        }
+    }
+
+    // The real part brought into [min, max], the imaginary part is kept
+    pub fn clamp_real(self, min: f32, max: f32) -> Complex {
+        Complex {
+            real: self.real.max(min).min(max),
+            imag: self.imag,
+        }
+    }
+
+    // Each part in [0, 1)
+    pub fn frac(self) -> Complex {
+        self - self.floor()
+    }
+
+    // z/|z| with the same argument, zero stays zero, used by the sign instruction
+    pub fn normalize(self) -> Complex {
+        if self.is_zero() {
+            Complex::ZERO
+        } else {
+            self / Complex::from_real(self.modulus())
+        }
+    }
+
+    pub fn log2(self) -> Complex {
+        self.log() / LN_2
+    }
+
+    pub fn log10(self) -> Complex {
+        self.log() / LN_10
+    }
+
+    // Principal square root from the modulus, without going through log and exp
+    pub fn sqrt_exact(self) -> Complex {
+        if self.is_zero() {
+            return Complex::ZERO;
+        }
+
+        // The larger part has no cancellation, the other one follows from 2 re im = imag
+        let modulus = self.modulus();
+        if self.real >= 0. {
+            let real = sqrtf((modulus + self.real) / 2.);
+            Complex {
+                real,
+                imag: self.imag / (2. * real),
+            }
+        } else {
+            let imag = copysignf(sqrtf((modulus - self.real) / 2.), self.imag);
+            Complex {
+                real: self.imag / (2. * imag),
+                imag,
+            }
+        }
+    }
+
+    // Principal cube root, its branch cut is the negative real axis like the one of the argument
+    pub fn cbrt(self) -> Complex {
+        Complex::from_polar(cbrtf(self.modulus()), self.argument() / 3.)
+    }
+
+    // The one with the largest modulus, self on a tie
+    pub fn max_by_modulus(self, other: Complex) -> Complex {
+        if self.squared_modulus() >= other.squared_modulus() {
+            self
+        } else {
+            other
+        }
+    }
+
+    // The one with the smallest modulus, self on a tie
+    pub fn min_by_modulus(self, other: Complex) -> Complex {
+        if self.squared_modulus() <= other.squared_modulus() {
+            self
+        } else {
+            other
+        }
+    }
+
+    pub fn reciprocal(self) -> Complex {
+        Complex::from_real(1.) / self
+    }
+
+    pub fn erf(self) -> Complex {
+        if self.is_real() {
+            let x = fabsf(self.real);
+            let t = 1. / (1. + ERF_P * x);
+            let poly = ERF_COEFFICIENTS
+                .iter()
+                .rev()
+                .fold(0., |poly, a| (poly + a) * t);
+            return Complex::from_real(copysignf(1. - poly * expf(-x * x), self.real));
+        }
+
+        // Each term is the previous one times -z² / (n + 1), before the division by 2n + 1
+        let z2 = self * self;
+        let (sum, _) = (0..ERF_TERMS).fold((Complex::ZERO, self), |(sum, power), n| {
+            (
+                sum + power / (2 * n + 1) as f32,
+                -power * z2 / (n + 1) as f32,
+            )
+        });
+        sum * FRAC_2_SQRT_PI
+    }
+
+    pub fn zeta(self, terms: u32) -> Complex {
+        if self.real < 0.5 {
+            // Functional equation
+            return Complex::from_real(2.).pow(self)
+                * Complex::from_real(PI).pow(self - 1.)
+                * (self * PI / 2.).sin()
+                * (1. - self).gamma()
+                * (1. - self).zeta(terms);
+        }
+
+        // Euler-Maclaurin summation
+        let n = terms as f32;
+        let partial_sum =
+            (1..terms).fold(Complex::ZERO, |sum, k| sum + (-self * logf(k as f32)).exp());
+        let tail = (-self * logf(n)).exp() * (n / (self - 1.) + 0.5);
+
+        let (corrections, _, _) = ZETA_CORRECTIONS.iter().enumerate().fold(
+            (Complex::ZERO, self, (-self * logf(n)).exp() / n),
+            |(sum, factor, n_pow), (k, c)| {
+                let k = (k + 1) as f32;
+                (
+                    sum + factor * n_pow * *c,
+                    factor * (self + 2. * k - 1.) * (self + 2. * k),
+                    n_pow / (n * n),
+                )
+            },
+        );
+
+        partial_sum + tail + corrections
+    }
+}
+impl From<(f32, f32)> for Complex {
+    fn from((real, imag): (f32, f32)) -> Self {
+        Complex { real, imag }
+    }
+}
+impl From<Complex> for (f32, f32) {
+    fn from(z: Complex) -> Self {
+        (z.real, z.imag)
+    }
+}
+impl From<f32> for Complex {
+    fn from(real: f32) -> Self {
+        Complex::from_real(real)
+    }
+}
+// Compares moduli, so this isn't a total order: |a| == |b| doesn't mean a == b.
+// It is inconsistent on purpose with PartialEq, which compares both components exactly.
+impl PartialOrd for Complex {
+    fn partial_cmp(&self, other: &Complex) -> Option<Ordering> {
+        self.squared_modulus().partial_cmp(&other.squared_modulus())
+    }
 }
 impl Neg for Complex {
     type Output = Complex;
@@ -175,7 +540,7 @@ impl Sub<Complex> for f32 {
     fn sub(self, rhs: Complex) -> Complex {
         Complex {
             real: self - rhs.real,
-            imag: rhs.imag,
+            imag: -rhs.imag,
         }
     }
 }
@@ -193,7 +558,7 @@ impl Div<Complex> for f32 {
     fn div(self, rhs: Complex) -> Complex {
         self * Complex {
             real: rhs.real / (rhs.real * rhs.real + rhs.imag * rhs.imag),
-            imag: rhs.imag / (rhs.real * rhs.real + rhs.imag * rhs.imag),
+            imag: -rhs.imag / (rhs.real * rhs.real + rhs.imag * rhs.imag),
         }
     }
 }
@@ -242,6 +607,9 @@ pub trait Trig {
     fn sin(self) -> Self::Output;
     fn cos(self) -> Self::Output;
     fn tan(self) -> Self::Output;
+    fn cot(self) -> Self::Output;
+    fn sec(self) -> Self::Output;
+    fn csc(self) -> Self::Output;
 }
 pub trait InverseTrig {
     type Output;
@@ -250,6 +618,16 @@ pub trait InverseTrig {
     fn arccos(self) -> Self::Output;
     fn arctan(self) -> Self::Output;
 }
+pub trait Hyperbolic {
+    type Output;
+
+    fn sinh(self) -> Self::Output;
+}
+pub trait Gamma {
+    type Output;
+
+    fn gamma(self) -> Self::Output;
+}
 pub trait Conj {
     type Output;
 
@@ -328,6 +706,15 @@ impl Trig for Complex {
         let exp_minus_iz = (-Complex::I * self).exp();
         -Complex::I * (exp_iz - exp_minus_iz) / (exp_iz + exp_minus_iz)
     }
+    fn cot(self) -> Complex {
+        self.cos() / self.sin()
+    }
+    fn sec(self) -> Complex {
+        Complex::from_real(1.) / self.cos()
+    }
+    fn csc(self) -> Complex {
+        Complex::from_real(1.) / self.sin()
+    }
 }
 impl Trig for f32 {
     type Output = f32;
@@ -341,6 +728,15 @@ impl Trig for f32 {
     fn tan(self) -> f32 {
         tanf(self)
     }
+    fn cot(self) -> f32 {
+        1. / tanf(self)
+    }
+    fn sec(self) -> f32 {
+        1. / cosf(self)
+    }
+    fn csc(self) -> f32 {
+        1. / sinf(self)
+    }
 }
 
 impl InverseTrig for Complex {
@@ -370,6 +766,48 @@ impl InverseTrig for f32 {
     }
 }
 
+impl Hyperbolic for Complex {
+    type Output = Complex;
+
+    fn sinh(self) -> Complex {
+        (self.exp() - (-self).exp()) / 2.
+    }
+}
+impl Hyperbolic for f32 {
+    type Output = f32;
+
+    fn sinh(self) -> f32 {
+        sinhf(self)
+    }
+}
+
+impl Gamma for Complex {
+    type Output = Complex;
+
+    fn gamma(self) -> Complex {
+        if self.real < 0.5 {
+            // Reflection formula
+            PI / ((self * PI).sin() * (1. - self).gamma())
+        } else {
+            let z = self - 1.;
+            let x = LANCZOS_COEFFICIENTS.iter().enumerate().skip(1).fold(
+                Complex::from_real(LANCZOS_COEFFICIENTS[0] as f32),
+                |x, (i, c)| x + *c as f32 / (z + i as f32),
+            );
+            let t = z + LANCZOS_G + 0.5;
+
+            SQRT_TAU * t.pow(z + 0.5) * (-t).exp() * x
+        }
+    }
+}
+impl Gamma for f32 {
+    type Output = f32;
+
+    fn gamma(self) -> f32 {
+        tgammaf(self)
+    }
+}
+
 impl Conj for Complex {
     type Output = Complex;
 
@@ -387,3 +825,37 @@ impl Conj for f32 {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_rect() {
+        let unit = ComplexRect::unit();
+        assert_eq!(unit.center(), Complex::ZERO);
+        assert_eq!((unit.width(), unit.height()), (2., 2.));
+        assert!(unit.is_valid());
+    }
+
+    #[test]
+    fn invalid_rects() {
+        let unit = ComplexRect::unit();
+        let infinite = ComplexRect {
+            to_imag: f32::INFINITY,
+            ..unit
+        };
+        let nan = ComplexRect {
+            from_real: f32::NAN,
+            ..unit
+        };
+        let flipped = ComplexRect {
+            from_real: unit.to_real,
+            to_real: unit.from_real,
+            ..unit
+        };
+        assert!(!infinite.is_valid());
+        assert!(!nan.is_valid());
+        assert!(!flipped.is_valid());
+    }
+}
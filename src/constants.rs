@@ -0,0 +1,74 @@
+use core::fmt::Write;
+
+use heapless::String;
+
+use crate::eadk::{
+    display::{self, SCREEN_WIDTH},
+    key, keyboard, timing, Color, Point, Rect,
+};
+
+use crate::function::USER_CONSTANTS;
+use crate::plot::{plot_func, plot_rect};
+use crate::utils::{keyboard_complex, number_or_cursor, wait_till_released, CHARACTER_HEIGHT};
+
+use crate::State;
+
+// Type new values for the A to D constants, UP and DOWN pick the one set by EXE
+pub fn edit_constants(state: &mut State) {
+    let mut input: String<40> = String::new();
+    let mut selected = 0;
+    let mut changed = false;
+    let header = Rect {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: CHARACTER_HEIGHT * (USER_CONSTANTS as u16 + 1),
+    };
+
+    wait_till_released(key::TOOLBOX);
+
+    loop {
+        display::push_rect_uniform(header, Color::WHITE);
+
+        let mut s: String<320> = String::new();
+        for (i, c) in state.constants.iter().enumerate() {
+            let marker = if i == selected { '>' } else { ' ' };
+            writeln!(&mut s, "{}{} = {}", marker, (b'A' + i as u8) as char, c).unwrap();
+        }
+        write!(
+            &mut s,
+            "new {} = {}\0",
+            (b'A' + selected as u8) as char,
+            number_or_cursor(&input)
+        )
+        .unwrap();
+        display::draw_string(&s, Point::ZERO, false, Color::BLACK, Color::WHITE);
+
+        let keyboard_state = keyboard::scan();
+        if keyboard_state.key_down(key::BACK) {
+            wait_till_released(key::BACK);
+            break;
+        } else if keyboard_state.key_down(key::UP) && selected > 0 {
+            wait_till_released(key::UP);
+            selected -= 1;
+            input.clear();
+        } else if keyboard_state.key_down(key::DOWN) && selected + 1 < USER_CONSTANTS {
+            wait_till_released(key::DOWN);
+            selected += 1;
+            input.clear();
+        } else if let Some(c) = keyboard_complex(&mut input) {
+            state.constants[selected] = c;
+            input.clear();
+            changed = true;
+        }
+
+        timing::msleep(100);
+        display::wait_for_vblank();
+    }
+
+    if changed {
+        plot_func(state);
+    } else {
+        plot_rect(state, header, 0, 1, 0);
+    }
+}
@@ -29,8 +29,23 @@ impl Color {
 
     #[must_use]
     pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        // Undefined or infinite results of the plotted function end up here
+        let hue = if hue.is_finite() { hue } else { 0. };
+        let clamp = |x: f32| {
+            if x.is_nan() || x < 0. {
+                0.
+            } else if x > 1. {
+                1.
+            } else {
+                x
+            }
+        };
+        let (saturation, value) = (clamp(saturation), clamp(value));
+
         let f = |n: f32| {
+            // Wrap hues outside of (-pi, pi]
             let k: f32 = (n + hue / PI * 3.) % 6.;
+            let k = if k < 0. { k + 6. } else { k };
             value * (1. - saturation * k.min(4. - k).min(1.).max(0.))
         };
         Color::from_rgb888(
@@ -42,15 +57,7 @@ impl Color {
 
     #[must_use]
     pub fn from_hv(hue: f32, value: f32) -> Self {
-        let f = |n: f32| {
-            let k: f32 = (n + hue / PI * 3.) % 6.;
-            value * (1. - k.min(4. - k).min(1.).max(0.))
-        };
-        Color::from_rgb888(
-            (f(5.) * 255.) as u8,
-            (f(3.) * 255.) as u8,
-            (f(1.) * 255.) as u8,
-        )
+        Color::from_hsv(hue, 1., value)
     }
 }
 
@@ -80,7 +87,7 @@ impl Point {
 }
 
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct State(u64);
 
 impl State {
@@ -180,6 +187,12 @@ pub mod display {
         }
     }
 
+    pub fn pull_rect(rect: Rect, pixels: &mut [Color]) {
+        unsafe {
+            eadk_display_pull_rect(rect, pixels.as_mut_ptr());
+        }
+    }
+
     pub fn push_rect_uniform(rect: Rect, color: Color) {
         unsafe {
             eadk_display_push_rect_uniform(rect, color);
@@ -207,6 +220,7 @@ pub mod display {
     extern "C" {
         fn eadk_display_push_rect_uniform(rect: Rect, color: Color);
         fn eadk_display_push_rect(rect: Rect, color: *const Color);
+        fn eadk_display_pull_rect(rect: Rect, color: *mut Color);
         fn eadk_display_draw_string(
             text: *const u8,
             pos: Point,
@@ -265,40 +279,46 @@ extern "C" {
     fn eadk_random() -> u32;
 }
 
-use core::fmt::Write;
-use core::panic::PanicInfo;
+// The app shows where it panicked, tests run on the host with the standard handler
+#[cfg(not(test))]
+mod panic_handler {
+    use core::fmt::Write;
+    use core::panic::PanicInfo;
+
+    use heapless::String;
+
+    use super::{display, Color, Point, Rect};
+
+    #[panic_handler]
+    fn panic(info: &PanicInfo<'_>) -> ! {
+        let mut panic_str: String<64> = String::new();
+        if let Some(location) = info.location() {
+            write!(
+                &mut panic_str,
+                "Error\nin {}\nline {}\0",
+                location.file(),
+                location.line()
+            )
+            .unwrap();
+        }
 
-use heapless::String;
+        display::push_rect_uniform(
+            Rect {
+                x: 0,
+                y: 0,
+                width: display::SCREEN_WIDTH,
+                height: display::SCREEN_HEIGHT,
+            },
+            Color::RED,
+        );
+        display::draw_string(
+            &panic_str,
+            Point::new(0, 0),
+            true,
+            Color::BLACK,
+            Color::WHITE,
+        );
 
-#[panic_handler]
-fn panic(info: &PanicInfo<'_>) -> ! {
-    let mut panic_str: String<64> = String::new();
-    if let Some(location) = info.location() {
-        write!(
-            &mut panic_str,
-            "Error\nin {}\nline {}\0",
-            location.file(),
-            location.line()
-        )
-        .unwrap();
+        loop {}
     }
-
-    display::push_rect_uniform(
-        Rect {
-            x: 0,
-            y: 0,
-            width: display::SCREEN_WIDTH,
-            height: display::SCREEN_HEIGHT,
-        },
-        Color::RED,
-    );
-    display::draw_string(
-        &panic_str,
-        Point::new(0, 0),
-        true,
-        Color::BLACK,
-        Color::WHITE,
-    );
-
-    loop {}
 }
@@ -1,4 +1,4 @@
-use core::fmt::Write;
+use core::{convert::TryFrom, fmt::Write, mem};
 
 use heapless::String;
 
@@ -7,17 +7,25 @@ use crate::eadk::timing;
 use crate::eadk::{key, keyboard};
 use crate::eadk::{Color, Point, Rect};
 
-use crate::function::{FastFunction, MathInstruction, StringFunction, SyntaxError, Validate};
+use crate::function::{FastFunction, Function, MathInstruction, OptimizationStats};
+use crate::function::{StringFunction, Validate};
+use crate::function::{CLAMP_RANGES, EVAL_STACK_SIZE, FUNCTION_STRING_SIZE};
+use crate::function::{MAX_SAFE_STACK_DEPTH, USER_CONSTANTS};
 
-use crate::plot::{plot_func, plot_rect};
-use crate::utils::{keyboard_number, CHARACTER_WIDTH};
+use crate::plot::{draw_warning, plot_func, plot_rect};
+use crate::utils::{keyboard_number, number_or_cursor, wait_till_released, CHARACTER_WIDTH};
 use crate::utils::{CHARACTERS_BY_LINE, CHARACTER_HEIGHT};
 
 use crate::State;
 
+// With SHIFT + ALPHA, the keys of the letters A to D
+const USER_CONST_KEYS: [u32; USER_CONSTANTS] = [key::EXP, key::LN, key::LOG, key::IMAGINARY];
+
 pub fn editor(state: &mut State) {
     let mut max_line_count = 1;
     let previous_body = state.func_body.clone();
+    // The last key pressed added a clamp, pressing it again changes its range
+    let mut last_was_clamp = false;
 
     loop {
         let keyboard_state = keyboard::scan();
@@ -70,24 +78,142 @@ pub fn editor(state: &mut State) {
             Color::WHITE,
         );
 
-        if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::EXP) {
-            state.func_body.push(MathInstruction::E).unwrap();
+        // Close to what the evaluation stack can hold, under the function until it's cleared
+        let depth = state.func_body.max_stack_depth();
+        if depth > MAX_SAFE_STACK_DEPTH {
+            let mut message: String<48> = String::new();
+            write!(
+                &mut message,
+                "Stack depth {} of {}\0",
+                depth,
+                EVAL_STACK_SIZE - 1
+            )
+            .unwrap();
+            display::draw_string(
+                &message,
+                Point::new(0, line_count * CHARACTER_HEIGHT),
+                false,
+                Color::RED,
+                Color::WHITE,
+            );
+            max_line_count = max_line_count.max(line_count + 1);
+        }
+
+        // Err when the instruction typed this frame didn't fit
+        let mut pushed = Ok(());
+        // Cleared by anything done this frame, except adding another clamp
+        let clamp_before = mem::take(&mut last_was_clamp);
+        let user_const = USER_CONST_KEYS
+            .iter()
+            .position(|k| keyboard_state.key_down(*k));
+
+        if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::VAR)
+        {
+            // Hidden, lists the optimized instructions with how each changes the stack size
+            if state.func_body.validate().is_ok() {
+                max_line_count = max_line_count.max(show_optimized(state));
+            }
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::VAR) {
+            if state.func_body.validate().is_ok() {
+                max_line_count = max_line_count.max(show_infix(state));
+            }
+        } else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::SINE)
+        {
+            pushed = state.func_body.push_safe(MathInstruction::Csc);
+        } else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::COSINE)
+        {
+            pushed = state.func_body.push_safe(MathInstruction::Sec);
+        } else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::TANGENT)
+        {
+            pushed = state.func_body.push_safe(MathInstruction::Cot);
+        } else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::SEVEN)
+        {
+            pushed = state.func_body.push_safe(MathInstruction::Min);
+        } else if let Some(i) = user_const
+            .filter(|_| keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::ALPHA))
+        {
+            pushed = state
+                .func_body
+                .push_safe(MathInstruction::UserConst(i as u8));
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::SEVEN) {
+            pushed = state.func_body.push_safe(MathInstruction::Max);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::LOG) {
+            pushed = state.func_body.push_safe(MathInstruction::C);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::PI) {
+            pushed = state.func_body.push_safe(MathInstruction::Tau);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::PI) {
+            pushed = state.func_body.push_safe(MathInstruction::HalfPi);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::SQRT) {
+            pushed = state.func_body.push_safe(MathInstruction::Cbrt);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::EXP) {
+            pushed = state.func_body.push_safe(MathInstruction::E);
         } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::SINE) {
-            state.func_body.push(MathInstruction::Arcsin).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Arcsin);
         } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::COSINE) {
-            state.func_body.push(MathInstruction::Arccos).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Arccos);
         } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::TANGENT) {
-            state.func_body.push(MathInstruction::Arctan).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Arctan);
         } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::MINUS) {
-            state.func_body.push(MathInstruction::Conj).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Conj);
         } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::XNT) {
-            state.func_body.push(MathInstruction::ConjZ).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::ConjZ);
         } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::FOUR) {
-            state.func_body.push(MathInstruction::Re).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Re);
         } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::TANGENT) {
-            state.func_body.push(MathInstruction::Im).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Im);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::SINE) {
+            pushed = state.func_body.push_safe(MathInstruction::Sinh);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::DOT) {
+            pushed = state.func_body.push_safe(MathInstruction::Gamma);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::DIVISION) {
+            pushed = state.func_body.push_safe(MathInstruction::Recip);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::PLUS) {
+            pushed = state.func_body.push_safe(MathInstruction::Zeta);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::POWER) {
+            pushed = state.func_body.push_safe(MathInstruction::Floor);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::POWER) {
+            pushed = state.func_body.push_safe(MathInstruction::Ceil);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::FIVE) {
+            pushed = state.func_body.push_safe(MathInstruction::Sign);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::IMAGINARY) {
+            pushed = state.func_body.push_safe(MathInstruction::Cis);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::SQUARE) {
+            pushed = state.func_body.push_safe(MathInstruction::Abs2);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::LN) {
+            pushed = state.func_body.push_safe(MathInstruction::Log2);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::LOG) {
+            pushed = state.func_body.push_safe(MathInstruction::Log10);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::MINUS) {
+            pushed = state.func_body.push_safe(MathInstruction::Neg);
+        } else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::DOT) {
+            pushed = state.func_body.push_safe(MathInstruction::Frac);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::NINE) {
+            pushed = state.func_body.push_safe(MathInstruction::Over);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::EXP) {
+            pushed = state.func_body.push_safe(MathInstruction::Erf);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::SQRT) {
+            // Pressed again right after, the clamp moves to the next range
+            match state.func_body.last_mut() {
+                Some(MathInstruction::Clamp(i)) if clamp_before => {
+                    *i = (*i + 1) % CLAMP_RANGES.len() as u8;
+                }
+                _ => pushed = state.func_body.push_safe(MathInstruction::Clamp(0)),
+            }
+            last_was_clamp = pushed.is_ok();
+            // Held, it would go through the ranges every frame
+            wait_till_released(key::SQRT);
         } else if keyboard_state.key_down(key::BACKSPACE) {
-            state.func_body.pop();
+            state.func_body.pop_safe();
             display::push_rect_uniform(
                 Rect {
                     x: 0,
@@ -98,38 +224,39 @@ pub fn editor(state: &mut State) {
                 Color::WHITE,
             );
         } else if keyboard_state.key_down(key::XNT) {
-            state.func_body.push(MathInstruction::Z).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Z);
         } else if keyboard_state.key_down(key::IMAGINARY) {
-            state.func_body.push(MathInstruction::Imag).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Imag);
         } else if keyboard_state.key_down(key::PI) {
-            state.func_body.push(MathInstruction::Pi).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Pi);
         } else if keyboard_state.key_down(key::PLUS) {
-            state.func_body.push(MathInstruction::Add).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Add);
         } else if keyboard_state.key_down(key::MINUS) {
-            state.func_body.push(MathInstruction::Sub).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Sub);
         } else if keyboard_state.key_down(key::MULTIPLICATION) {
-            state.func_body.push(MathInstruction::Mul).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Mul);
         } else if keyboard_state.key_down(key::DIVISION) {
-            state.func_body.push(MathInstruction::Div).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Div);
         } else if keyboard_state.key_down(key::POWER) {
-            state.func_body.push(MathInstruction::Pow).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Pow);
         } else if keyboard_state.key_down(key::EXP) {
-            state.func_body.push(MathInstruction::Exp).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Exp);
         } else if keyboard_state.key_down(key::LN) {
-            state.func_body.push(MathInstruction::Ln).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Ln);
         } else if keyboard_state.key_down(key::LOG) {
-            state.func_body.push(MathInstruction::Log).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Log);
         } else if keyboard_state.key_down(key::SINE) {
-            state.func_body.push(MathInstruction::Sin).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Sin);
         } else if keyboard_state.key_down(key::COSINE) {
-            state.func_body.push(MathInstruction::Cos).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Cos);
         } else if keyboard_state.key_down(key::TANGENT) {
-            state.func_body.push(MathInstruction::Tan).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Tan);
         } else if keyboard_state.key_down(key::SQUARE) {
-            state.func_body.push(MathInstruction::Number(2.)).unwrap();
-            state.func_body.push(MathInstruction::Pow).unwrap();
+            pushed = state
+                .func_body
+                .extend_from_slice(&[MathInstruction::Number(2.), MathInstruction::Pow]);
         } else if keyboard_state.key_down(key::SQRT) {
-            state.func_body.push(MathInstruction::Sqrt).unwrap();
+            pushed = state.func_body.push_safe(MathInstruction::Sqrt);
         } else if number_pressed {
             let mut num: String<32> = String::new();
             loop {
@@ -144,11 +271,11 @@ pub fn editor(state: &mut State) {
                 );
 
                 let mut num_str: String<33> = String::new();
-                write!(&mut num_str, "{}\0", num).unwrap();
+                write!(&mut num_str, "{}\0", number_or_cursor(&num)).unwrap();
                 display::draw_string(&num_str, Point::ZERO, false, Color::WHITE, Color::BLACK);
 
                 if let Some(num) = keyboard_number(&mut num) {
-                    state.func_body.push(MathInstruction::Number(num)).unwrap();
+                    pushed = state.func_body.push_safe(MathInstruction::Number(num));
                     break;
                 }
 
@@ -165,6 +292,7 @@ pub fn editor(state: &mut State) {
                 Color::WHITE,
             );
         } else if keyboard_state.key_down(key::BACK) {
+            wait_till_released(key::BACK);
             state.func_body = previous_body;
             plot_rect(
                 state,
@@ -174,24 +302,40 @@ pub fn editor(state: &mut State) {
                     width: SCREEN_WIDTH,
                     height: max_line_count * CHARACTER_HEIGHT,
                 },
+                0,
+                1,
+                0,
             );
             break;
         } else if keyboard_state.key_down(key::OK) {
-            if let Err(SyntaxError { op_index: index }) = state.func_body.validate() {
-                if index == usize::MAX {
-                    display::draw_string(
-                        string.as_str(),
-                        Point::new(0, 0),
-                        false,
-                        Color::RED,
-                        Color::WHITE,
-                    );
-                    timing::msleep(400);
-                } else {
+            if let Err(errors) = state.func_body.validate_with_errors() {
+                // What is wrong with the first error, on the line under the function
+                let mut message: String<64> = String::new();
+                write!(&mut message, "{}\0", errors.first).unwrap();
+                display::draw_string(
+                    message.as_str(),
+                    Point::new(0, line_count * CHARACTER_HEIGHT),
+                    false,
+                    Color::RED,
+                    Color::WHITE,
+                );
+                max_line_count = max_line_count.max(line_count + 1);
+
+                display::draw_string(
+                    string.as_str(),
+                    Point::new(0, 0),
+                    false,
+                    Color::BLACK,
+                    Color::WHITE,
+                );
+
+                for index in errors.op_indices.iter().map(|index| *index as usize) {
+                    // Where the instruction starts
                     let (x, y) = string
                         .split_inclusive(' ')
                         .into_iter()
                         .scan((0, 0), |(x, y), el| {
+                            let start = (*x, *y);
                             el.chars().for_each(|c| {
                                 if c == '\n' {
                                     *x = 0;
@@ -200,7 +344,7 @@ pub fn editor(state: &mut State) {
                                     *x += 1;
                                 }
                             });
-                            Some((*x, *y))
+                            Some(start)
                         })
                         .nth(index)
                         .unwrap();
@@ -209,29 +353,184 @@ pub fn editor(state: &mut State) {
                     write!(&mut syntax_error_str, "{}\0", state.func_body[index]).unwrap();
 
                     display::draw_string(
-                        string.as_str(),
-                        Point::new(0, 0),
+                        syntax_error_str.as_str(),
+                        Point::new(x as u16 * CHARACTER_WIDTH, y as u16 * CHARACTER_HEIGHT),
                         false,
-                        Color::BLACK,
+                        Color::RED,
                         Color::WHITE,
                     );
+                }
+                if errors.not_singleton {
                     display::draw_string(
-                        syntax_error_str.as_str(),
-                        Point::new(x as u16 * CHARACTER_WIDTH, y as u16 * CHARACTER_HEIGHT),
+                        string.as_str(),
+                        Point::new(0, 0),
                         false,
                         Color::RED,
                         Color::WHITE,
                     );
+                    timing::msleep(400);
                 }
             } else {
-                state.func = FastFunction::from(state.func_body.clone());
+                let (func, stats) = FastFunction::optimize_with_stats(state.func_body.clone());
+                state.func = func;
+
+                // Unchanged or still the identity, only the editor needs to be cleared
+                if state.func_body == previous_body
+                    || (previous_body.is_identity() && state.func.is_identity())
+                {
+                    plot_rect(
+                        state,
+                        Rect {
+                            x: 0,
+                            y: 0,
+                            width: SCREEN_WIDTH,
+                            height: max_line_count * CHARACTER_HEIGHT,
+                        },
+                        0,
+                        1,
+                        0,
+                    );
+                    break;
+                }
 
                 plot_func(state);
+                if stats.final_len < stats.original_len {
+                    show_optimization_stats(state, &stats);
+                }
+                // The plot is a single color, tell why
+                if state.func.is_constant() {
+                    draw_warning("Warning: f is constant\0");
+                }
                 break;
             }
+        } else {
+            // Nothing pressed but SHIFT or ALPHA
+            last_was_clamp = clamp_before;
+        }
+
+        if pushed.is_err() {
+            display::draw_string(
+                "Function is full\0",
+                Point::new(0, line_count * CHARACTER_HEIGHT),
+                false,
+                Color::RED,
+                Color::WHITE,
+            );
+            max_line_count = max_line_count.max(line_count + 1);
+            timing::msleep(400);
         }
 
         timing::msleep(100);
         display::wait_for_vblank();
     }
 }
+
+// Draw the optimized function until a key is pressed, returns the number of lines used
+// The optimized instructions come first, then the same function written with the editor's
+fn show_optimized(state: &State) -> u16 {
+    let mut line_count = 1;
+    let mut s: StringFunction = StringFunction::new();
+    let mut line_length = 0;
+
+    // Tokens that don't fit are dropped, an empty token starts a new line
+    let mut push_token = |token: &str| {
+        // Keep room for the newline and the final \0
+        if s.len() + token.len() + 2 > s.capacity() {
+            return;
+        }
+
+        if token.is_empty() || line_length + token.chars().count() >= CHARACTERS_BY_LINE {
+            line_count += 1;
+            line_length = 0;
+            s.push('\n').unwrap();
+        }
+        line_length += token.chars().count();
+        s.push_str(token).unwrap();
+    };
+
+    let fast = FastFunction::from(state.func_body.clone());
+    for instr in fast.iter() {
+        let mut token: String<128> = String::new();
+        write!(&mut token, "{}[{:+}] ", instr, instr.stack_delta()).unwrap();
+        push_token(&token);
+    }
+    push_token("");
+    match Function::try_from(fast) {
+        Ok(func) => {
+            for instr in func.iter() {
+                let mut token: String<128> = String::new();
+                write!(&mut token, "{} ", instr).unwrap();
+                push_token(&token);
+            }
+        }
+        Err(()) => push_token("Too long to write back"),
+    }
+    s.push('\0').unwrap();
+
+    show_until_key(&s, line_count)
+}
+
+// Draw the function as a formula until a key is pressed, returns the number of lines used
+fn show_infix(state: &State) -> u16 {
+    let mut line_count = 1;
+    let mut s: String<{ FUNCTION_STRING_SIZE + FUNCTION_STRING_SIZE / CHARACTERS_BY_LINE + 2 }> =
+        String::new();
+
+    // The formula has no spaces to break lines at
+    for (i, c) in state.func_body.to_infix_string().chars().enumerate() {
+        if i > 0 && i % CHARACTERS_BY_LINE == 0 {
+            line_count += 1;
+            s.push('\n').unwrap();
+        }
+        s.push(c).unwrap();
+    }
+    s.push('\0').unwrap();
+
+    show_until_key(&s, line_count)
+}
+
+// Draw s over the top of the editor until a key is pressed
+fn show_until_key(s: &str, line_count: u16) -> u16 {
+    let area = Rect {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: line_count * CHARACTER_HEIGHT,
+    };
+    display::push_rect_uniform(area, Color::WHITE);
+    display::draw_string(s, Point::ZERO, false, Color::BLUE, Color::WHITE);
+
+    while keyboard::scan().any_down() {}
+    while !keyboard::scan().any_down() {}
+    while keyboard::scan().any_down() {}
+
+    // The editor draws itself again on the next frame
+    display::push_rect_uniform(area, Color::WHITE);
+    line_count
+}
+
+// Shown for a second over the top of the plot, which is then redrawn
+fn show_optimization_stats(state: &State, stats: &OptimizationStats) {
+    let mut message: String<96> = String::new();
+    write!(
+        &mut message,
+        "Optimized: {} → {} instructions\n{} constants folded in {} passes\0",
+        stats.original_len, stats.final_len, stats.constants_folded, stats.passes
+    )
+    .unwrap();
+    display::draw_string(&message, Point::ZERO, false, Color::BLACK, Color::WHITE);
+
+    timing::msleep(1000);
+    plot_rect(
+        state,
+        Rect {
+            x: 0,
+            y: 0,
+            width: SCREEN_WIDTH,
+            height: 2 * CHARACTER_HEIGHT,
+        },
+        0,
+        1,
+        0,
+    );
+}
@@ -0,0 +1,62 @@
+use libm::log2f;
+
+use crate::eadk::Color;
+
+use crate::complex::Complex;
+
+use crate::plot::complex_to_color::sigmoid;
+
+// Iterate z² + c until it escapes, the smooth escape time is max_iter when it never does
+pub fn julia_iterate(mut z: Complex, c: Complex, max_iter: u32) -> f32 {
+    for n in 0..max_iter {
+        let abs2 = z.norm_sq();
+        if abs2 > 4. {
+            return n as f32 + 1. - log2f(log2f(abs2) / 2.);
+        }
+        z = z * z + c;
+    }
+    max_iter as f32
+}
+
+pub fn mandelbrot_iterate(c: Complex, max_iter: u32) -> f32 {
+    julia_iterate(Complex::ZERO, c, max_iter)
+}
+
+// Step of the numerical derivative used by Newton's method
+const NEWTON_STEP: f32 = 1e-4;
+
+// Run Newton's method on f from z, giving the point reached and the iterations it took
+pub fn newton_iterate(
+    f: impl Fn(Complex) -> Complex,
+    mut z: Complex,
+    max_iter: u32,
+    eps: f32,
+) -> (Complex, u32) {
+    for n in 0..max_iter {
+        let fz = f(z);
+        if fz.squared_modulus() < eps * eps {
+            return (z, n);
+        }
+        let dfz = (f(z + NEWTON_STEP) - f(z - NEWTON_STEP)) / (2. * NEWTON_STEP);
+        z -= fz / dfz;
+    }
+    (z, max_iter)
+}
+
+// The hue tells which root was reached, the brightness how fast
+pub fn newton_color(z: Complex, n: u32, max_iter: u32, saturation: f32) -> Color {
+    if n >= max_iter {
+        Color::BLACK
+    } else {
+        Color::from_hsv(z.argument(), saturation, 1. - n as f32 / max_iter as f32)
+    }
+}
+
+pub fn escape_time_color(n: f32, max_iter: u32, saturation: f32) -> Color {
+    if n >= max_iter as f32 {
+        Color::BLACK
+    } else {
+        // The hue cycles slowly with the escape time while quick escapes stay dark
+        sigmoid(Complex::from_polar(n / 2., n / 4.), saturation)
+    }
+}
@@ -1,19 +1,37 @@
 use core::{
-    f32::consts::{E, PI},
+    convert::TryFrom,
+    f32::consts::{E, FRAC_PI_2, LN_10, LN_2, PI, TAU},
     fmt::{Display, Write},
     iter::{FromIterator, IntoIterator},
+    mem,
     ops::{Deref, DerefMut},
     slice,
 };
 
 use heapless::{String, Vec};
+use libm::expf;
 
-use crate::complex::{Complex, Conj, Exp, InverseTrig, Log, Pow, Trig};
+use crate::complex::{
+    Complex, Conj, Exp, Gamma, Hyperbolic, InverseTrig, Log, Pow, Trig, ZETA_TERMS,
+};
 
 pub const FUNCTION_SIZE: usize = 255;
 pub const FUNCTION_STRING_SIZE: usize = FUNCTION_SIZE * 8;
-
-#[derive(Clone)]
+// The first slot is never used, the stack pointer starts below the first value
+pub const EVAL_STACK_SIZE: usize = 32;
+// Deeper functions are still valid up to EVAL_STACK_SIZE - 1 but the editor warns about them
+pub const MAX_SAFE_STACK_DEPTH: usize = 30;
+// Bounds of the real part for clamp, the instruction only holds an index to stay as small as a number
+pub const CLAMP_RANGES: [(f32, f32); 4] = [
+    (0., 1.),
+    (-1., 1.),
+    (0., f32::INFINITY),
+    (f32::NEG_INFINITY, 0.),
+];
+// Constants set by the user besides c, named A to D
+pub const USER_CONSTANTS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Function {
     instructions: Vec<MathInstruction, FUNCTION_SIZE>,
 }
@@ -26,6 +44,9 @@ pub type StringFunction = String<FUNCTION_STRING_SIZE>;
 #[derive(Clone, Debug)]
 pub enum MathInstruction {
     Z,
+    C,
+    // Index in EvalContext::constants
+    UserConst(u8),
     Number(f32),
 
     ConjZ,
@@ -35,6 +56,8 @@ pub enum MathInstruction {
 
     Imag,
     Pi,
+    Tau,
+    HalfPi,
     E,
 
     Add,
@@ -44,10 +67,15 @@ pub enum MathInstruction {
     Pow,
 
     Sqrt,
+    Cbrt,
+    Recip,
+    Neg,
 
     Exp,
     Ln,
     Log,
+    Log2,
+    Log10,
 
     Sin,
     Cos,
@@ -56,11 +84,36 @@ pub enum MathInstruction {
     Arcsin,
     Arccos,
     Arctan,
+
+    Cot,
+    Sec,
+    Csc,
+
+    Sinh,
+    Gamma,
+    Zeta,
+    Erf,
+
+    Floor,
+    Ceil,
+    Frac,
+    // Index in CLAMP_RANGES
+    Clamp(u8),
+
+    Sign,
+    Abs2,
+    Cis,
+
+    Over,
+    Max,
+    Min,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum FastMathInstr {
     Z,
+    C,
+    UserConst(u8),
     Number(Complex),
 
     ConjZ,
@@ -94,6 +147,14 @@ pub enum FastMathInstr {
     DivS,
     PowS,
 
+    CbrtZ,
+    RecipZ,
+    NegZ,
+
+    Cbrt,
+    Recip,
+    Neg,
+
     ExpZ,
     LnZ,
 
@@ -120,12 +181,55 @@ pub enum FastMathInstr {
     Arcsin,
     Arccos,
     Arctan,
+
+    CotZ,
+    SecZ,
+    CscZ,
+
+    Cot,
+    Sec,
+    Csc,
+
+    SinhZ,
+    GammaZ,
+    ZetaZ,
+    ErfZ,
+
+    Sinh,
+    Gamma,
+    Zeta,
+    Erf,
+
+    FloorZ,
+    CeilZ,
+    FracZ,
+
+    Floor,
+    Ceil,
+    Frac,
+    Clamp(f32, f32),
+
+    SignZ,
+    Abs2Z,
+    CisZ,
+
+    Sign,
+    Abs2,
+    Cis,
+
+    Over,
+    Max(Complex),
+    Min(Complex),
+    MaxS,
+    MinS,
 }
 
 impl Display for MathInstruction {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             MathInstruction::Z => write!(f, "Z"),
+            MathInstruction::C => write!(f, "c"),
+            MathInstruction::UserConst(i) => write!(f, "{}", (b'A' + i) as char),
             MathInstruction::Number(x) => write!(f, "{}", x),
 
             MathInstruction::ConjZ => write!(f, "Z*"),
@@ -135,6 +239,8 @@ impl Display for MathInstruction {
 
             MathInstruction::Imag => write!(f, "i"),
             MathInstruction::Pi => write!(f, "pi"),
+            MathInstruction::Tau => write!(f, "tau"),
+            MathInstruction::HalfPi => write!(f, "pi/2"),
             MathInstruction::E => write!(f, "e"),
 
             MathInstruction::Add => write!(f, "+"),
@@ -157,6 +263,252 @@ impl Display for MathInstruction {
             MathInstruction::Arcsin => write!(f, "arcsin"),
             MathInstruction::Arccos => write!(f, "arccos"),
             MathInstruction::Arctan => write!(f, "arctan"),
+
+            MathInstruction::Sinh => write!(f, "sinh"),
+
+            MathInstruction::Gamma => write!(f, "gamma"),
+
+            MathInstruction::Recip => write!(f, "1/"),
+
+            MathInstruction::Zeta => write!(f, "zeta"),
+
+            MathInstruction::Floor => write!(f, "floor"),
+
+            MathInstruction::Ceil => write!(f, "ceil"),
+
+            MathInstruction::Sign => write!(f, "sign"),
+
+            MathInstruction::Cis => write!(f, "cis"),
+
+            MathInstruction::Abs2 => write!(f, "abs2"),
+
+            MathInstruction::Cot => write!(f, "cot"),
+
+            MathInstruction::Sec => write!(f, "sec"),
+
+            MathInstruction::Csc => write!(f, "csc"),
+
+            MathInstruction::Log2 => write!(f, "log2"),
+            MathInstruction::Log10 => write!(f, "log10"),
+
+            MathInstruction::Neg => write!(f, "neg"),
+            MathInstruction::Frac => write!(f, "frac"),
+            MathInstruction::Over => write!(f, "over"),
+            MathInstruction::Max => write!(f, "max"),
+            MathInstruction::Min => write!(f, "min"),
+            MathInstruction::Erf => write!(f, "erf"),
+            MathInstruction::Clamp(i) => {
+                let (min, max) = CLAMP_RANGES[*i as usize];
+                write!(f, "clamp[{},{}]", min, max)
+            }
+            MathInstruction::Cbrt => write!(f, "cbrt"),
+        }
+    }
+}
+
+// Short tokens for debugging the optimizer, c(..) and r(..) are complex and real constants
+impl Display for FastMathInstr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FastMathInstr::Z => write!(f, "Z"),
+            FastMathInstr::C => write!(f, "c"),
+            FastMathInstr::UserConst(i) => write!(f, "{}", (b'A' + i) as char),
+            FastMathInstr::Number(c) => write!(f, "{}", c),
+
+            FastMathInstr::ConjZ => write!(f, "Z*"),
+            FastMathInstr::Conj => write!(f, "_"),
+            FastMathInstr::ReZ => write!(f, "ReZ"),
+            FastMathInstr::ImZ => write!(f, "ImZ"),
+            FastMathInstr::Re => write!(f, "Re"),
+            FastMathInstr::Im => write!(f, "Im"),
+
+            FastMathInstr::Add(c) => write!(f, "+c({})", c),
+            FastMathInstr::Sub(c) => write!(f, "-c({})", c),
+            FastMathInstr::Mul(c) => write!(f, "*c({})", c),
+            FastMathInstr::Div(c) => write!(f, "/c({})", c),
+            FastMathInstr::Pow(c) => write!(f, "^c({})", c),
+            FastMathInstr::AddR(x) => write!(f, "+r({})", x),
+            FastMathInstr::SubR(x) => write!(f, "-r({})", x),
+            FastMathInstr::MulR(x) => write!(f, "*r({})", x),
+            FastMathInstr::DivR(x) => write!(f, "/r({})", x),
+            FastMathInstr::PowR(x) => write!(f, "^r({})", x),
+            FastMathInstr::AddZ => write!(f, "+Z"),
+            FastMathInstr::SubZ => write!(f, "-Z"),
+            FastMathInstr::MulZ => write!(f, "*Z"),
+            FastMathInstr::DivZ => write!(f, "/Z"),
+            FastMathInstr::PowZ => write!(f, "^Z"),
+            FastMathInstr::AddS => write!(f, "+"),
+            FastMathInstr::SubS => write!(f, "-"),
+            FastMathInstr::MulS => write!(f, "*"),
+            FastMathInstr::DivS => write!(f, "/"),
+            FastMathInstr::PowS => write!(f, "^"),
+
+            FastMathInstr::Log(c) => write!(f, "logc({})", c),
+            FastMathInstr::LogR(x) => write!(f, "logr({})", x),
+            FastMathInstr::LogZ => write!(f, "logZ"),
+            FastMathInstr::LogS => write!(f, "log"),
+
+            FastMathInstr::ExpZ => write!(f, "e^Z"),
+            FastMathInstr::Exp => write!(f, "e^"),
+            FastMathInstr::LnZ => write!(f, "lnZ"),
+            FastMathInstr::Ln => write!(f, "ln"),
+            FastMathInstr::SinZ => write!(f, "sinZ"),
+            FastMathInstr::Sin => write!(f, "sin"),
+            FastMathInstr::CosZ => write!(f, "cosZ"),
+            FastMathInstr::Cos => write!(f, "cos"),
+            FastMathInstr::TanZ => write!(f, "tanZ"),
+            FastMathInstr::Tan => write!(f, "tan"),
+            FastMathInstr::ArcsinZ => write!(f, "arcsinZ"),
+            FastMathInstr::Arcsin => write!(f, "arcsin"),
+            FastMathInstr::ArccosZ => write!(f, "arccosZ"),
+            FastMathInstr::Arccos => write!(f, "arccos"),
+            FastMathInstr::ArctanZ => write!(f, "arctanZ"),
+            FastMathInstr::Arctan => write!(f, "arctan"),
+            FastMathInstr::SinhZ => write!(f, "sinhZ"),
+            FastMathInstr::Sinh => write!(f, "sinh"),
+            FastMathInstr::GammaZ => write!(f, "gammaZ"),
+            FastMathInstr::Gamma => write!(f, "gamma"),
+            FastMathInstr::RecipZ => write!(f, "1/Z"),
+            FastMathInstr::Recip => write!(f, "1/"),
+            FastMathInstr::ZetaZ => write!(f, "zetaZ"),
+            FastMathInstr::Zeta => write!(f, "zeta"),
+            FastMathInstr::FloorZ => write!(f, "floorZ"),
+            FastMathInstr::Floor => write!(f, "floor"),
+            FastMathInstr::CeilZ => write!(f, "ceilZ"),
+            FastMathInstr::Ceil => write!(f, "ceil"),
+            FastMathInstr::SignZ => write!(f, "signZ"),
+            FastMathInstr::Sign => write!(f, "sign"),
+            FastMathInstr::CisZ => write!(f, "cisZ"),
+            FastMathInstr::Cis => write!(f, "cis"),
+            FastMathInstr::Abs2Z => write!(f, "abs2Z"),
+            FastMathInstr::Abs2 => write!(f, "abs2"),
+            FastMathInstr::CotZ => write!(f, "cotZ"),
+            FastMathInstr::Cot => write!(f, "cot"),
+            FastMathInstr::SecZ => write!(f, "secZ"),
+            FastMathInstr::Sec => write!(f, "sec"),
+            FastMathInstr::CscZ => write!(f, "cscZ"),
+            FastMathInstr::Csc => write!(f, "csc"),
+            FastMathInstr::NegZ => write!(f, "negZ"),
+            FastMathInstr::Neg => write!(f, "neg"),
+            FastMathInstr::FracZ => write!(f, "fracZ"),
+            FastMathInstr::Frac => write!(f, "frac"),
+            FastMathInstr::Over => write!(f, "over"),
+            FastMathInstr::Max(c) => write!(f, "maxc({})", c),
+            FastMathInstr::Min(c) => write!(f, "minc({})", c),
+            FastMathInstr::MaxS => write!(f, "max"),
+            FastMathInstr::MinS => write!(f, "min"),
+            FastMathInstr::ErfZ => write!(f, "erfZ"),
+            FastMathInstr::Erf => write!(f, "erf"),
+            FastMathInstr::Clamp(min, max) => write!(f, "clamp[{},{}]", min, max),
+            FastMathInstr::CbrtZ => write!(f, "cbrtZ"),
+            FastMathInstr::Cbrt => write!(f, "cbrt"),
+        }
+    }
+}
+
+// Numbers compare by their bits, so a NaN constant equals itself and 0 differs from -0
+impl PartialEq for MathInstruction {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MathInstruction::Number(a), MathInstruction::Number(b)) => a.to_bits() == b.to_bits(),
+            (MathInstruction::Clamp(a), MathInstruction::Clamp(b))
+            | (MathInstruction::UserConst(a), MathInstruction::UserConst(b)) => a == b,
+            // The other variants hold nothing
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+impl Eq for MathInstruction {}
+
+impl MathInstruction {
+    // Number of values taken from the stack
+    fn arity(&self) -> usize {
+        match self {
+            MathInstruction::Z
+            | MathInstruction::C
+            | MathInstruction::UserConst(_)
+            | MathInstruction::Number(_)
+            | MathInstruction::ConjZ
+            | MathInstruction::Pi
+            | MathInstruction::Tau
+            | MathInstruction::HalfPi
+            | MathInstruction::E => 0,
+
+            MathInstruction::Add
+            | MathInstruction::Sub
+            | MathInstruction::Mul
+            | MathInstruction::Div
+            | MathInstruction::Pow
+            | MathInstruction::Log
+            | MathInstruction::Over
+            | MathInstruction::Max
+            | MathInstruction::Min => 2,
+
+            _ => 1,
+        }
+    }
+
+    // Number of values pushed back, over gives back both operands along with the copy
+    fn pushed(&self) -> usize {
+        match self {
+            MathInstruction::Over => 3,
+            _ => 1,
+        }
+    }
+
+    // Single byte identifying the instruction in the binary format
+    fn opcode(&self) -> u8 {
+        match self {
+            MathInstruction::Z => 0,
+            MathInstruction::Number(_) => 1,
+            MathInstruction::ConjZ => 2,
+            MathInstruction::Conj => 3,
+            MathInstruction::Re => 4,
+            MathInstruction::Im => 5,
+            MathInstruction::Imag => 6,
+            MathInstruction::Pi => 7,
+            MathInstruction::E => 8,
+            MathInstruction::Add => 9,
+            MathInstruction::Sub => 10,
+            MathInstruction::Mul => 11,
+            MathInstruction::Div => 12,
+            MathInstruction::Pow => 13,
+            MathInstruction::Sqrt => 14,
+            MathInstruction::Exp => 15,
+            MathInstruction::Ln => 16,
+            MathInstruction::Log => 17,
+            MathInstruction::Sin => 18,
+            MathInstruction::Cos => 19,
+            MathInstruction::Tan => 20,
+            MathInstruction::Arcsin => 21,
+            MathInstruction::Arccos => 22,
+            MathInstruction::Arctan => 23,
+            MathInstruction::Sinh => 24,
+            MathInstruction::Gamma => 25,
+            MathInstruction::Recip => 26,
+            MathInstruction::Zeta => 27,
+            MathInstruction::Floor => 28,
+            MathInstruction::Ceil => 29,
+            MathInstruction::Sign => 30,
+            MathInstruction::Cis => 31,
+            MathInstruction::Abs2 => 32,
+            MathInstruction::Cot => 33,
+            MathInstruction::Sec => 34,
+            MathInstruction::Csc => 35,
+            MathInstruction::Log2 => 36,
+            MathInstruction::Log10 => 37,
+            MathInstruction::Tau => 38,
+            MathInstruction::HalfPi => 39,
+            MathInstruction::C => 40,
+            MathInstruction::Neg => 41,
+            MathInstruction::Frac => 42,
+            MathInstruction::Over => 43,
+            MathInstruction::Max => 44,
+            MathInstruction::Min => 45,
+            MathInstruction::Erf => 46,
+            MathInstruction::Clamp(_) => 47,
+            MathInstruction::UserConst(_) => 48,
+            MathInstruction::Cbrt => 49,
         }
     }
 }
@@ -169,6 +521,15 @@ impl From<&[MathInstruction]> for Function {
     }
 }
 
+// Instructions past FUNCTION_SIZE are dropped, see Function::try_from_iter
+impl FromIterator<MathInstruction> for Function {
+    fn from_iter<T: IntoIterator<Item = MathInstruction>>(iter: T) -> Self {
+        Self {
+            instructions: iter.into_iter().take(FUNCTION_SIZE).collect(),
+        }
+    }
+}
+
 impl FromIterator<FastMathInstr> for FastFunction {
     fn from_iter<T: IntoIterator<Item = FastMathInstr>>(iter: T) -> Self {
         Self {
@@ -234,17 +595,503 @@ impl DerefMut for FastFunction {
     }
 }
 
+impl Function {
+    // f(z) = z
+    pub fn identity() -> Function {
+        Function::from(&[MathInstruction::Z][..])
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.len() == 1 && matches!(self[0], MathInstruction::Z)
+    }
+
+    // f(z) = c, leaving out the parts of c that are zero
+    pub fn constant(c: Complex) -> Function {
+        if c.is_real() {
+            Function::from(&[MathInstruction::Number(c.real)][..])
+        } else if c.real == 0. {
+            Function::from(&[MathInstruction::Number(c.imag), MathInstruction::Imag][..])
+        } else {
+            Function::from(
+                &[
+                    MathInstruction::Number(c.real),
+                    MathInstruction::Number(c.imag),
+                    MathInstruction::Imag,
+                    MathInstruction::Add,
+                ][..],
+            )
+        }
+    }
+
+    // Err when the function already has FUNCTION_SIZE instructions
+    pub fn push_safe(&mut self, instr: MathInstruction) -> Result<(), ()> {
+        self.instructions.push(instr).map_err(|_| ())
+    }
+
+    // None when the function is empty
+    pub fn pop_safe(&mut self) -> Option<MathInstruction> {
+        self.instructions.pop()
+    }
+
+    // All or nothing, Err without any change when they don't fit
+    pub fn extend_from_slice(&mut self, instrs: &[MathInstruction]) -> Result<(), ()> {
+        self.instructions.extend_from_slice(instrs)
+    }
+
+    // None if there are more than FUNCTION_SIZE instructions
+    pub fn try_from_iter<T: IntoIterator<Item = MathInstruction>>(iter: T) -> Option<Function> {
+        let mut func = Function::default();
+        for instr in iter {
+            func.push(instr).ok()?;
+        }
+        Some(func)
+    }
+
+    // Number of Z and ConjZ instructions
+    pub fn count_z_references(&self) -> usize {
+        self.iter()
+            .filter(|instr| matches!(instr, MathInstruction::Z | MathInstruction::ConjZ))
+            .count()
+    }
+
+    // Whether the output doesn't depend on z
+    pub fn is_constant(&self) -> bool {
+        self.count_z_references() == 0
+    }
+
+    // Most values on the stack at once while evaluating
+    pub fn max_stack_depth(&self) -> usize {
+        max_depth(self.iter().map(|instr| (instr.arity(), instr.pushed())))
+    }
+
+    // Each instruction is stored as its opcode, numbers are followed by their little endian bits
+    // and clamps and user constants by their index, None if that doesn't fit in N bytes
+    pub fn to_bytes<const N: usize>(&self) -> Option<Vec<u8, N>> {
+        let mut bytes = Vec::new();
+
+        for instr in self.iter() {
+            bytes.push(instr.opcode()).ok()?;
+            match instr {
+                MathInstruction::Number(x) => {
+                    bytes.extend_from_slice(&x.to_bits().to_le_bytes()).ok()?
+                }
+                MathInstruction::Clamp(i) | MathInstruction::UserConst(i) => bytes.push(*i).ok()?,
+                _ => {}
+            }
+        }
+
+        Some(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Function> {
+        let mut func = Function::default();
+        let mut iter = bytes.iter();
+
+        while let Some(opcode) = iter.next() {
+            let instr = match opcode {
+                0 => MathInstruction::Z,
+                1 => {
+                    let mut bits = [0; 4];
+                    for byte in bits.iter_mut() {
+                        *byte = *iter.next()?;
+                    }
+                    MathInstruction::Number(f32::from_bits(u32::from_le_bytes(bits)))
+                }
+                2 => MathInstruction::ConjZ,
+                3 => MathInstruction::Conj,
+                4 => MathInstruction::Re,
+                5 => MathInstruction::Im,
+                6 => MathInstruction::Imag,
+                7 => MathInstruction::Pi,
+                8 => MathInstruction::E,
+                9 => MathInstruction::Add,
+                10 => MathInstruction::Sub,
+                11 => MathInstruction::Mul,
+                12 => MathInstruction::Div,
+                13 => MathInstruction::Pow,
+                14 => MathInstruction::Sqrt,
+                15 => MathInstruction::Exp,
+                16 => MathInstruction::Ln,
+                17 => MathInstruction::Log,
+                18 => MathInstruction::Sin,
+                19 => MathInstruction::Cos,
+                20 => MathInstruction::Tan,
+                21 => MathInstruction::Arcsin,
+                22 => MathInstruction::Arccos,
+                23 => MathInstruction::Arctan,
+                24 => MathInstruction::Sinh,
+                25 => MathInstruction::Gamma,
+                26 => MathInstruction::Recip,
+                27 => MathInstruction::Zeta,
+                28 => MathInstruction::Floor,
+                29 => MathInstruction::Ceil,
+                30 => MathInstruction::Sign,
+                31 => MathInstruction::Cis,
+                32 => MathInstruction::Abs2,
+                33 => MathInstruction::Cot,
+                34 => MathInstruction::Sec,
+                35 => MathInstruction::Csc,
+                36 => MathInstruction::Log2,
+                37 => MathInstruction::Log10,
+                38 => MathInstruction::Tau,
+                39 => MathInstruction::HalfPi,
+                40 => MathInstruction::C,
+                41 => MathInstruction::Neg,
+                42 => MathInstruction::Frac,
+                43 => MathInstruction::Over,
+                44 => MathInstruction::Max,
+                45 => MathInstruction::Min,
+                46 => MathInstruction::Erf,
+                47 => {
+                    let i = *iter.next()?;
+                    if i as usize >= CLAMP_RANGES.len() {
+                        return None;
+                    }
+                    MathInstruction::Clamp(i)
+                }
+                48 => {
+                    let i = *iter.next()?;
+                    if i as usize >= USER_CONSTANTS {
+                        return None;
+                    }
+                    MathInstruction::UserConst(i)
+                }
+                49 => MathInstruction::Cbrt,
+                _ => return None,
+            };
+            func.push(instr).ok()?;
+        }
+
+        func.validate().ok()?;
+        Some(func)
+    }
+
+    // outer(inner(z)), obtained by replacing every z of outer by inner
+    pub fn composition(outer: &Function, inner: &Function) -> Option<Function> {
+        outer.validate().ok()?;
+        inner.validate().ok()?;
+
+        let mut func = Function::default();
+        for instr in outer.iter() {
+            match instr {
+                MathInstruction::Z => func.extend_from_slice(inner).ok()?,
+                MathInstruction::ConjZ => {
+                    func.extend_from_slice(inner).ok()?;
+                    func.push(MathInstruction::Conj).ok()?;
+                }
+                i => func.push(i.clone()).ok()?,
+            }
+        }
+
+        Some(func)
+    }
+
+    // Human readable form, the function has to be valid
+    pub fn to_infix_string(&self) -> StringFunction {
+        let mut s = StringFunction::new();
+
+        if self.validate().is_ok() && self.write_infix(self.len() - 1, &mut s).is_err() {
+            let mut len = s.capacity() - '…'.len_utf8();
+            while !s.is_char_boundary(len) {
+                len -= 1;
+            }
+            s.truncate(len);
+            s.push('…').unwrap();
+        }
+
+        s
+    }
+
+    // Index of the first instruction of the subexpression ending at end
+    fn subexpression_start(&self, end: usize) -> usize {
+        let mut needed = 1;
+        let mut start = end;
+
+        loop {
+            needed = needed + self[start].arity() - self[start].pushed();
+            if needed == 0 {
+                return start;
+            }
+            start -= 1;
+        }
+    }
+
+    fn write_infix(&self, end: usize, s: &mut StringFunction) -> core::fmt::Result {
+        let instr = &self[end];
+
+        // The copy is the value under the top one
+        if let MathInstruction::Over = instr {
+            return self.write_infix(self.subexpression_start(end - 1) - 1, s);
+        }
+
+        match instr.arity() {
+            0 => match instr {
+                MathInstruction::Z => write!(s, "z"),
+                MathInstruction::C => write!(s, "c"),
+                MathInstruction::ConjZ => write!(s, "z*"),
+                i => write!(s, "{}", i),
+            },
+            1 => {
+                match instr {
+                    MathInstruction::Imag => write!(s, "(")?,
+                    MathInstruction::Conj => write!(s, "conj(")?,
+                    i => write!(s, "{}(", i)?,
+                }
+                self.write_infix(end - 1, s)?;
+                match instr {
+                    MathInstruction::Imag => write!(s, "i)"),
+                    _ => write!(s, ")"),
+                }
+            }
+            _ => {
+                let lhs_end = self.subexpression_start(end - 1) - 1;
+
+                match instr {
+                    MathInstruction::Log | MathInstruction::Max | MathInstruction::Min => {
+                        write!(s, "{}(", instr)?
+                    }
+                    _ => write!(s, "(")?,
+                }
+                self.write_infix(lhs_end, s)?;
+                match instr {
+                    MathInstruction::Log | MathInstruction::Max | MathInstruction::Min => {
+                        write!(s, ", ")?
+                    }
+                    i => write!(s, "{}", i)?,
+                }
+                self.write_infix(end - 1, s)?;
+                write!(s, ")")
+            }
+        }
+    }
+}
+
+// Write functions in code, FunctionBuilder::new().z().number(2.).pow().sin() is sin(z^2)
+// Only tests build functions this way
+#[cfg(test)]
+#[derive(Default)]
+pub struct FunctionBuilder {
+    func: Function,
+    // An instruction didn't fit, the following ones are dropped
+    too_long: bool,
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+pub enum BuildError {
+    TooLong,
+    Syntax(SyntaxError),
+}
+
+#[cfg(test)]
+impl FunctionBuilder {
+    pub fn new() -> Self {
+        FunctionBuilder::default()
+    }
+
+    fn push(mut self, instr: MathInstruction) -> Self {
+        self.too_long |= self.func.push(instr).is_err();
+        self
+    }
+
+    pub fn z(self) -> Self {
+        self.push(MathInstruction::Z)
+    }
+
+    pub fn user_const(self, i: u8) -> Self {
+        self.push(MathInstruction::UserConst(i))
+    }
+
+    pub fn number(self, x: f32) -> Self {
+        self.push(MathInstruction::Number(x))
+    }
+
+    pub fn conj(self) -> Self {
+        self.push(MathInstruction::Conj)
+    }
+
+    pub fn add(self) -> Self {
+        self.push(MathInstruction::Add)
+    }
+
+    pub fn mul(self) -> Self {
+        self.push(MathInstruction::Mul)
+    }
+
+    pub fn pow(self) -> Self {
+        self.push(MathInstruction::Pow)
+    }
+
+    pub fn sin(self) -> Self {
+        self.push(MathInstruction::Sin)
+    }
+
+    pub fn neg(self) -> Self {
+        self.push(MathInstruction::Neg)
+    }
+
+    pub fn clamp(self, range: u8) -> Self {
+        self.push(MathInstruction::Clamp(range))
+    }
+
+    pub fn build(self) -> Result<Function, BuildError> {
+        if self.too_long {
+            return Err(BuildError::TooLong);
+        }
+        self.func.validate().map_err(BuildError::Syntax)?;
+        Ok(self.func)
+    }
+}
+
+impl FastMathInstr {
+    // How the stack size changes after the instruction
+    pub fn stack_delta(&self) -> i8 {
+        self.pushed() as i8 - self.arity() as i8
+    }
+
+    // Number of values taken from the stack
+    fn arity(&self) -> usize {
+        match self {
+            FastMathInstr::Z
+            | FastMathInstr::C
+            | FastMathInstr::UserConst(_)
+            | FastMathInstr::Number(_)
+            | FastMathInstr::ConjZ
+            | FastMathInstr::ReZ
+            | FastMathInstr::ImZ
+            | FastMathInstr::ExpZ
+            | FastMathInstr::LnZ
+            | FastMathInstr::SinZ
+            | FastMathInstr::CosZ
+            | FastMathInstr::TanZ
+            | FastMathInstr::ArcsinZ
+            | FastMathInstr::ArccosZ
+            | FastMathInstr::ArctanZ
+            | FastMathInstr::SinhZ
+            | FastMathInstr::GammaZ
+            | FastMathInstr::RecipZ
+            | FastMathInstr::ZetaZ
+            | FastMathInstr::FloorZ
+            | FastMathInstr::CeilZ
+            | FastMathInstr::SignZ
+            | FastMathInstr::CisZ
+            | FastMathInstr::Abs2Z
+            | FastMathInstr::CotZ
+            | FastMathInstr::SecZ
+            | FastMathInstr::CscZ
+            | FastMathInstr::NegZ
+            | FastMathInstr::FracZ
+            | FastMathInstr::ErfZ
+            | FastMathInstr::CbrtZ => 0,
+
+            FastMathInstr::AddS
+            | FastMathInstr::SubS
+            | FastMathInstr::MulS
+            | FastMathInstr::DivS
+            | FastMathInstr::PowS
+            | FastMathInstr::LogS
+            | FastMathInstr::Over
+            | FastMathInstr::MaxS
+            | FastMathInstr::MinS => 2,
+
+            _ => 1,
+        }
+    }
+
+    fn pushed(&self) -> usize {
+        match self {
+            FastMathInstr::Over => 3,
+            _ => 1,
+        }
+    }
+}
+
+impl FastFunction {
+    pub fn is_identity(&self) -> bool {
+        self.len() == 1 && matches!(self[0], FastMathInstr::Z)
+    }
+
+    pub fn map<F: Fn(FastMathInstr) -> FastMathInstr>(&self, f: F) -> FastFunction {
+        self.iter().cloned().map(f).collect()
+    }
+
+    // Number of instructions reading z
+    pub fn count_z_references(&self) -> usize {
+        self.iter()
+            .filter(|instr| {
+                matches!(
+                    instr,
+                    FastMathInstr::Z
+                        | FastMathInstr::ConjZ
+                        | FastMathInstr::ReZ
+                        | FastMathInstr::ImZ
+                        | FastMathInstr::AddZ
+                        | FastMathInstr::SubZ
+                        | FastMathInstr::MulZ
+                        | FastMathInstr::DivZ
+                        | FastMathInstr::PowZ
+                        | FastMathInstr::ExpZ
+                        | FastMathInstr::LnZ
+                        | FastMathInstr::LogZ
+                        | FastMathInstr::SinZ
+                        | FastMathInstr::CosZ
+                        | FastMathInstr::TanZ
+                        | FastMathInstr::ArcsinZ
+                        | FastMathInstr::ArccosZ
+                        | FastMathInstr::ArctanZ
+                        | FastMathInstr::SinhZ
+                        | FastMathInstr::GammaZ
+                        | FastMathInstr::RecipZ
+                        | FastMathInstr::ZetaZ
+                        | FastMathInstr::FloorZ
+                        | FastMathInstr::CeilZ
+                        | FastMathInstr::SignZ
+                        | FastMathInstr::CisZ
+                        | FastMathInstr::Abs2Z
+                        | FastMathInstr::CotZ
+                        | FastMathInstr::SecZ
+                        | FastMathInstr::CscZ
+                        | FastMathInstr::NegZ
+                        | FastMathInstr::FracZ
+                        | FastMathInstr::ErfZ
+                        | FastMathInstr::CbrtZ
+                )
+            })
+            .count()
+    }
+
+    // Whether the output doesn't depend on z
+    pub fn is_constant(&self) -> bool {
+        self.count_z_references() == 0
+    }
+
+    pub fn max_stack_depth(&self) -> usize {
+        max_depth(self.iter().map(|instr| (instr.arity(), instr.pushed())))
+    }
+}
+
+// Values the variables of a function take
+#[derive(Clone, Copy, Debug)]
+pub struct EvalContext {
+    pub z: Complex,
+    pub c: Complex,
+    pub constants: [Complex; USER_CONSTANTS],
+}
+
 pub trait Evaluate {
-    fn eval(&self, z: Complex) -> Complex;
+    fn eval(&self, ctx: &EvalContext) -> Complex;
 }
 
 impl Evaluate for Function {
-    fn eval(&self, z: Complex) -> Complex {
+    fn eval(&self, ctx: &EvalContext) -> Complex {
+        let z = ctx.z;
         let mut stack: Vec<Complex, 32> = Vec::new();
 
         for instr in self.iter() {
             match instr {
                 MathInstruction::Z => stack.push(z).unwrap(),
+                MathInstruction::C => stack.push(ctx.c).unwrap(),
+                MathInstruction::UserConst(i) => stack.push(ctx.constants[*i as usize]).unwrap(),
                 MathInstruction::Number(x) => stack.push(Complex::from_real(*x)).unwrap(),
 
                 MathInstruction::ConjZ => stack.push(z.conj()).unwrap(),
@@ -267,6 +1114,8 @@ impl Evaluate for Function {
                     stack.push(c * Complex::from_imag(1.)).unwrap();
                 }
                 MathInstruction::Pi => stack.push(Complex::from_real(PI)).unwrap(),
+                MathInstruction::Tau => stack.push(Complex::from_real(TAU)).unwrap(),
+                MathInstruction::HalfPi => stack.push(Complex::from_real(FRAC_PI_2)).unwrap(),
                 MathInstruction::E => stack.push(Complex::from_real(E)).unwrap(),
 
                 MathInstruction::Add => {
@@ -297,7 +1146,7 @@ impl Evaluate for Function {
 
                 MathInstruction::Sqrt => {
                     let c = stack.pop().unwrap();
-                    stack.push(c.pow(0.5)).unwrap();
+                    stack.push(c.sqrt_exact()).unwrap();
                 }
 
                 MathInstruction::Exp => {
@@ -340,6 +1189,117 @@ impl Evaluate for Function {
                     let c = stack.pop().unwrap();
                     stack.push(c.arctan()).unwrap();
                 }
+
+                MathInstruction::Sinh => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.sinh()).unwrap();
+                }
+
+                MathInstruction::Gamma => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.gamma()).unwrap();
+                }
+
+                MathInstruction::Recip => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.reciprocal()).unwrap();
+                }
+
+                MathInstruction::Zeta => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.zeta(ZETA_TERMS)).unwrap();
+                }
+
+                MathInstruction::Floor => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.floor()).unwrap();
+                }
+
+                MathInstruction::Ceil => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.ceil()).unwrap();
+                }
+
+                MathInstruction::Sign => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.normalize()).unwrap();
+                }
+
+                MathInstruction::Cis => {
+                    let c = stack.pop().unwrap();
+                    stack.push(Complex::cis(c.real)).unwrap();
+                }
+
+                MathInstruction::Abs2 => {
+                    let c = stack.pop().unwrap();
+                    stack.push(Complex::from_real(c.norm_sq())).unwrap();
+                }
+
+                MathInstruction::Cot => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.cot()).unwrap();
+                }
+
+                MathInstruction::Sec => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.sec()).unwrap();
+                }
+
+                MathInstruction::Csc => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.csc()).unwrap();
+                }
+
+                MathInstruction::Log2 => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.log2()).unwrap();
+                }
+                MathInstruction::Log10 => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.log10()).unwrap();
+                }
+
+                MathInstruction::Neg => {
+                    let c = stack.pop().unwrap();
+                    stack.push(-c).unwrap();
+                }
+
+                MathInstruction::Frac => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.frac()).unwrap();
+                }
+
+                MathInstruction::Over => {
+                    let c = stack[stack.len() - 2];
+                    stack.push(c).unwrap();
+                }
+
+                MathInstruction::Max => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(a.max_by_modulus(b)).unwrap();
+                }
+                MathInstruction::Min => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(a.min_by_modulus(b)).unwrap();
+                }
+
+                MathInstruction::Erf => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.erf()).unwrap();
+                }
+
+                MathInstruction::Clamp(i) => {
+                    let (min, max) = CLAMP_RANGES[*i as usize];
+                    let c = stack.pop().unwrap();
+                    stack.push(c.clamp_real(min, max)).unwrap();
+                }
+
+                MathInstruction::Cbrt => {
+                    let c = stack.pop().unwrap();
+                    stack.push(c.cbrt()).unwrap();
+                }
             }
         }
 
@@ -348,8 +1308,9 @@ impl Evaluate for Function {
 }
 
 impl Evaluate for FastFunction {
-    fn eval(&self, z: Complex) -> Complex {
-        let mut stack: [Complex; 32] = [Complex::ZERO; 32];
+    fn eval(&self, ctx: &EvalContext) -> Complex {
+        let z = ctx.z;
+        let mut stack = [Complex::ZERO; EVAL_STACK_SIZE];
         let mut stack_pointer = 0;
 
         for instr in self.iter() {
@@ -358,6 +1319,14 @@ impl Evaluate for FastFunction {
                     stack_pointer += 1;
                     stack[stack_pointer] = z;
                 }
+                FastMathInstr::C => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = ctx.c;
+                }
+                FastMathInstr::UserConst(i) => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = ctx.constants[*i as usize];
+                }
                 FastMathInstr::Number(c) => {
                     stack_pointer += 1;
                     stack[stack_pointer] = *c;
@@ -415,6 +1384,10 @@ impl Evaluate for FastFunction {
                 FastMathInstr::DivR(r) => {
                     stack[stack_pointer] /= *r;
                 }
+                // Square roots come here, the direct formula is faster than log and exp
+                FastMathInstr::PowR(r) if *r == 0.5 => {
+                    stack[stack_pointer] = stack[stack_pointer].sqrt_exact();
+                }
                 FastMathInstr::PowR(r) => {
                     stack[stack_pointer] = stack[stack_pointer].pow(*r);
                 }
@@ -532,84 +1505,329 @@ impl Evaluate for FastFunction {
                     stack_pointer += 1;
                     stack[stack_pointer] = z.arctan();
                 }
-            }
-        }
 
-        stack[1]
-    }
-}
+                FastMathInstr::Sinh => {
+                    stack[stack_pointer] = stack[stack_pointer].sinh();
+                }
+                FastMathInstr::SinhZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.sinh();
+                }
 
-impl<T: Fn(Complex) -> Complex> Evaluate for T {
-    fn eval(&self, z: Complex) -> Complex {
-        self(z)
-    }
+                FastMathInstr::Gamma => {
+                    stack[stack_pointer] = stack[stack_pointer].gamma();
+                }
+                FastMathInstr::GammaZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.gamma();
+                }
+
+                FastMathInstr::Recip => {
+                    stack[stack_pointer] = stack[stack_pointer].reciprocal();
+                }
+                FastMathInstr::RecipZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.reciprocal();
+                }
+
+                FastMathInstr::Zeta => {
+                    stack[stack_pointer] = stack[stack_pointer].zeta(ZETA_TERMS);
+                }
+                FastMathInstr::ZetaZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.zeta(ZETA_TERMS);
+                }
+
+                FastMathInstr::Floor => {
+                    stack[stack_pointer] = stack[stack_pointer].floor();
+                }
+                FastMathInstr::FloorZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.floor();
+                }
+
+                FastMathInstr::Ceil => {
+                    stack[stack_pointer] = stack[stack_pointer].ceil();
+                }
+                FastMathInstr::CeilZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.ceil();
+                }
+
+                FastMathInstr::Sign => {
+                    stack[stack_pointer] = stack[stack_pointer].normalize();
+                }
+                FastMathInstr::SignZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.normalize();
+                }
+
+                FastMathInstr::Cis => {
+                    stack[stack_pointer] = Complex::cis(stack[stack_pointer].real);
+                }
+                FastMathInstr::CisZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = Complex::cis(z.real);
+                }
+
+                FastMathInstr::Abs2 => {
+                    stack[stack_pointer] = Complex::from_real(stack[stack_pointer].norm_sq());
+                }
+                FastMathInstr::Abs2Z => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = Complex::from_real(z.norm_sq());
+                }
+
+                FastMathInstr::Cot => {
+                    stack[stack_pointer] = stack[stack_pointer].cot();
+                }
+                FastMathInstr::CotZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.cot();
+                }
+
+                FastMathInstr::Sec => {
+                    stack[stack_pointer] = stack[stack_pointer].sec();
+                }
+                FastMathInstr::SecZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.sec();
+                }
+
+                FastMathInstr::Csc => {
+                    stack[stack_pointer] = stack[stack_pointer].csc();
+                }
+                FastMathInstr::CscZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.csc();
+                }
+
+                FastMathInstr::Neg => {
+                    stack[stack_pointer] = -stack[stack_pointer];
+                }
+                FastMathInstr::NegZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = -z;
+                }
+
+                FastMathInstr::Frac => {
+                    stack[stack_pointer] = stack[stack_pointer].frac();
+                }
+                FastMathInstr::FracZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.frac();
+                }
+
+                FastMathInstr::Over => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = stack[stack_pointer - 2];
+                }
+
+                FastMathInstr::Max(c) => {
+                    stack[stack_pointer] = stack[stack_pointer].max_by_modulus(*c);
+                }
+                FastMathInstr::Min(c) => {
+                    stack[stack_pointer] = stack[stack_pointer].min_by_modulus(*c);
+                }
+                FastMathInstr::MaxS => {
+                    stack_pointer -= 1;
+                    stack[stack_pointer] =
+                        stack[stack_pointer].max_by_modulus(stack[stack_pointer + 1]);
+                }
+                FastMathInstr::MinS => {
+                    stack_pointer -= 1;
+                    stack[stack_pointer] =
+                        stack[stack_pointer].min_by_modulus(stack[stack_pointer + 1]);
+                }
+
+                FastMathInstr::Erf => {
+                    stack[stack_pointer] = stack[stack_pointer].erf();
+                }
+                FastMathInstr::ErfZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.erf();
+                }
+
+                FastMathInstr::Clamp(min, max) => {
+                    stack[stack_pointer] = stack[stack_pointer].clamp_real(*min, *max);
+                }
+
+                FastMathInstr::Cbrt => {
+                    stack[stack_pointer] = stack[stack_pointer].cbrt();
+                }
+                FastMathInstr::CbrtZ => {
+                    stack_pointer += 1;
+                    stack[stack_pointer] = z.cbrt();
+                }
+            }
+        }
+
+        stack[1]
+    }
+}
+
+impl<T: Fn(Complex) -> Complex> Evaluate for T {
+    fn eval(&self, ctx: &EvalContext) -> Complex {
+        self(ctx.z)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SyntaxErrorKind {
+    // An instruction lacks operands
+    Underflow,
+    // The evaluation stack would be too deep
+    Overflow,
+    // The function doesn't end with exactly one value
+    NotSingleton { actual: usize },
 }
 
+// op_index is usize::MAX when the error concerns the whole function
+#[derive(Clone, Copy, Debug)]
 pub struct SyntaxError {
     pub op_index: usize,
+    pub kind: SyntaxErrorKind,
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.kind {
+            SyntaxErrorKind::Underflow => {
+                write!(f, "Stack underflow at instruction {}", self.op_index)
+            }
+            SyntaxErrorKind::Overflow => {
+                write!(f, "Stack overflow at instruction {}", self.op_index)
+            }
+            SyntaxErrorKind::NotSingleton { actual } => {
+                write!(f, "Stack ends with {} values instead of 1", actual)
+            }
+        }
+    }
+}
+pub const MAX_SYNTAX_ERRORS: usize = 16;
+
+// Every error of a function, indices are u8 as they are under FUNCTION_SIZE
+#[derive(Clone, Debug)]
+pub struct SyntaxErrors {
+    pub first: SyntaxError,
+    // Instructions with an error, past MAX_SYNTAX_ERRORS they are dropped
+    pub op_indices: Vec<u8, MAX_SYNTAX_ERRORS>,
+    // The stack doesn't end with exactly one value
+    pub not_singleton: bool,
 }
+
 pub trait Validate {
     fn validate(&self) -> Result<(), SyntaxError>;
+
+    fn validate_with_errors(&self) -> Result<(), SyntaxErrors>;
+}
+
+// Simulate the stack size, after an error the stack is assumed to be in a valid state so the rest can be checked
+fn validate_arities(arities: impl Iterator<Item = (usize, usize)>) -> Result<(), SyntaxErrors> {
+    let mut errors: Option<SyntaxErrors> = None;
+    let mut report = |op_index: usize, kind: SyntaxErrorKind| {
+        let errors = errors.get_or_insert(SyntaxErrors {
+            first: SyntaxError { op_index, kind },
+            op_indices: Vec::new(),
+            not_singleton: false,
+        });
+        match kind {
+            SyntaxErrorKind::NotSingleton { .. } => errors.not_singleton = true,
+            _ => {
+                errors.op_indices.push(op_index as u8).ok();
+            }
+        }
+    };
+    let mut stack_size: usize = 0;
+
+    for (op_index, (arity, pushed)) in arities.enumerate() {
+        if stack_size < arity {
+            report(op_index, SyntaxErrorKind::Underflow);
+            stack_size = pushed;
+        } else if stack_size - arity + pushed >= EVAL_STACK_SIZE {
+            report(op_index, SyntaxErrorKind::Overflow);
+        } else {
+            stack_size = stack_size - arity + pushed;
+        }
+    }
+    if stack_size != 1 {
+        report(
+            usize::MAX,
+            SyntaxErrorKind::NotSingleton { actual: stack_size },
+        );
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+// Like validate_arities, an underflow restarts from what the instruction pushed
+fn max_depth(arities: impl Iterator<Item = (usize, usize)>) -> usize {
+    arities
+        .scan(0, |stack_size: &mut usize, (arity, pushed)| {
+            *stack_size = stack_size.saturating_sub(arity) + pushed;
+            Some(*stack_size)
+        })
+        .max()
+        .unwrap_or(0)
 }
 
 impl Validate for Function {
     fn validate(&self) -> Result<(), SyntaxError> {
-        let mut stack_size: isize = 0;
+        self.validate_with_errors().map_err(|errors| errors.first)
+    }
 
-        for (op_index, instr) in self.into_iter().enumerate() {
-            match instr {
-                MathInstruction::Number(_)
-                | MathInstruction::Pi
-                | MathInstruction::E
-                | MathInstruction::Z
-                | MathInstruction::ConjZ => stack_size += 1,
-
-                MathInstruction::Imag
-                | MathInstruction::Conj
-                | MathInstruction::Re
-                | MathInstruction::Im
-                | MathInstruction::Sqrt
-                | MathInstruction::Exp
-                | MathInstruction::Ln
-                | MathInstruction::Sin
-                | MathInstruction::Cos
-                | MathInstruction::Tan
-                | MathInstruction::Arcsin
-                | MathInstruction::Arccos
-                | MathInstruction::Arctan
-                    if stack_size > 0 => {}
-
-                MathInstruction::Add
-                | MathInstruction::Sub
-                | MathInstruction::Mul
-                | MathInstruction::Div
-                | MathInstruction::Pow
-                | MathInstruction::Log => stack_size -= 1,
-
-                _ => return Err(SyntaxError { op_index }),
-            }
+    fn validate_with_errors(&self) -> Result<(), SyntaxErrors> {
+        validate_arities(self.iter().map(|instr| (instr.arity(), instr.pushed())))
+    }
+}
 
-            if stack_size <= 0 {
-                return Err(SyntaxError { op_index });
-            }
-        }
-        if stack_size != 1 {
-            return Err(SyntaxError {
-                op_index: usize::MAX,
-            });
-        }
+// Catches optimizations breaking the stack invariant
+impl Validate for FastFunction {
+    fn validate(&self) -> Result<(), SyntaxError> {
+        self.validate_with_errors().map_err(|errors| errors.first)
+    }
 
-        Ok(())
+    fn validate_with_errors(&self) -> Result<(), SyntaxErrors> {
+        validate_arities(self.iter().map(|instr| (instr.arity(), instr.pushed())))
     }
 }
 
+// What the optimization of a function did, constants_folded counts the instructions computed ahead
+#[derive(Clone, Copy, Debug)]
+pub struct OptimizationStats {
+    pub original_len: usize,
+    pub final_len: usize,
+    pub passes: usize,
+    pub constants_folded: usize,
+}
+
 impl From<Function> for FastFunction {
     fn from(func: Function) -> Self {
+        FastFunction::optimize_with_stats(func).0
+    }
+}
+
+impl FastFunction {
+    pub fn optimize_with_stats(func: Function) -> (FastFunction, OptimizationStats) {
+        // Only checked in debug builds
+        let source_valid = cfg!(debug_assertions) && func.validate().is_ok();
+        let mut stats = OptimizationStats {
+            original_len: func.len(),
+            final_len: 0,
+            passes: 0,
+            constants_folded: 0,
+        };
+
         // MathInstr to FastMathInstr && Simplify Number -> Imag to Number
         let mut fast_instr: FastFunction = func
             .into_iter()
             .map(|instr| match *instr {
                 MathInstruction::Z => FastMathInstr::Z,
+                MathInstruction::C => FastMathInstr::C,
+                MathInstruction::UserConst(i) => FastMathInstr::UserConst(i),
                 MathInstruction::Number(x) => FastMathInstr::Number(Complex::from_real(x)),
                 MathInstruction::Imag => FastMathInstr::Mul(Complex::I),
 
@@ -619,6 +1837,8 @@ impl From<Function> for FastFunction {
                 MathInstruction::Im => FastMathInstr::Im,
 
                 MathInstruction::Pi => FastMathInstr::Number(Complex::from_real(PI)),
+                MathInstruction::Tau => FastMathInstr::Number(Complex::from_real(TAU)),
+                MathInstruction::HalfPi => FastMathInstr::Number(Complex::from_real(FRAC_PI_2)),
                 MathInstruction::E => FastMathInstr::Number(Complex::from_real(E)),
 
                 MathInstruction::Add => FastMathInstr::AddS,
@@ -641,6 +1861,52 @@ impl From<Function> for FastFunction {
                 MathInstruction::Arcsin => FastMathInstr::Arcsin,
                 MathInstruction::Arccos => FastMathInstr::Arccos,
                 MathInstruction::Arctan => FastMathInstr::Arctan,
+
+                MathInstruction::Sinh => FastMathInstr::Sinh,
+
+                MathInstruction::Gamma => FastMathInstr::Gamma,
+
+                MathInstruction::Recip => FastMathInstr::Recip,
+
+                MathInstruction::Zeta => FastMathInstr::Zeta,
+
+                MathInstruction::Floor => FastMathInstr::Floor,
+
+                MathInstruction::Ceil => FastMathInstr::Ceil,
+
+                MathInstruction::Sign => FastMathInstr::Sign,
+
+                MathInstruction::Cis => FastMathInstr::Cis,
+
+                MathInstruction::Abs2 => FastMathInstr::Abs2,
+
+                MathInstruction::Cot => FastMathInstr::Cot,
+
+                MathInstruction::Sec => FastMathInstr::Sec,
+
+                MathInstruction::Csc => FastMathInstr::Csc,
+
+                // Logarithms with a constant base
+                MathInstruction::Log2 => FastMathInstr::Log(Complex::from_real(LN_2)),
+                MathInstruction::Log10 => FastMathInstr::Log(Complex::from_real(LN_10)),
+
+                MathInstruction::Neg => FastMathInstr::Neg,
+
+                MathInstruction::Frac => FastMathInstr::Frac,
+
+                MathInstruction::Over => FastMathInstr::Over,
+
+                MathInstruction::Max => FastMathInstr::MaxS,
+                MathInstruction::Min => FastMathInstr::MinS,
+
+                MathInstruction::Erf => FastMathInstr::Erf,
+
+                MathInstruction::Clamp(i) => {
+                    let (min, max) = CLAMP_RANGES[i as usize];
+                    FastMathInstr::Clamp(min, max)
+                }
+
+                MathInstruction::Cbrt => FastMathInstr::Cbrt,
             })
             .collect();
 
@@ -648,6 +1914,7 @@ impl From<Function> for FastFunction {
 
         while fast_instr.len() < previous_len {
             previous_len = fast_instr.len();
+            stats.passes += 1;
 
             // Z && Number operation simplification
             let op_simplify = {
@@ -733,6 +2000,86 @@ impl From<Function> for FastFunction {
                                         FastMathInstr::ArctanZ
                                     }
 
+                                    FastMathInstr::Sinh => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::SinhZ
+                                    }
+
+                                    FastMathInstr::Gamma => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::GammaZ
+                                    }
+
+                                    FastMathInstr::Recip => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::RecipZ
+                                    }
+
+                                    FastMathInstr::Zeta => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::ZetaZ
+                                    }
+
+                                    FastMathInstr::Floor => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::FloorZ
+                                    }
+
+                                    FastMathInstr::Ceil => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::CeilZ
+                                    }
+
+                                    FastMathInstr::Sign => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::SignZ
+                                    }
+
+                                    FastMathInstr::Cis => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::CisZ
+                                    }
+
+                                    FastMathInstr::Abs2 => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Abs2Z
+                                    }
+
+                                    FastMathInstr::Cot => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::CotZ
+                                    }
+
+                                    FastMathInstr::Sec => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::SecZ
+                                    }
+
+                                    FastMathInstr::Csc => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::CscZ
+                                    }
+
+                                    FastMathInstr::Neg => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::NegZ
+                                    }
+
+                                    FastMathInstr::Frac => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::FracZ
+                                    }
+
+                                    FastMathInstr::Erf => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::ErfZ
+                                    }
+
+                                    FastMathInstr::Cbrt => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::CbrtZ
+                                    }
+
                                     _ => FastMathInstr::Z,
                                 }
                             } else {
@@ -768,6 +2115,15 @@ impl From<Function> for FastFunction {
                                         FastMathInstr::Log(x.log())
                                     }
 
+                                    FastMathInstr::MaxS => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Max(x)
+                                    }
+                                    FastMathInstr::MinS => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Min(x)
+                                    }
+
                                     _ => FastMathInstr::Number(x),
                                 }
                             } else {
@@ -783,6 +2139,7 @@ impl From<Function> for FastFunction {
             };
 
             // Compute number x number operations
+            let op_simplify_len = op_simplify.len();
             let pre_computed = {
                 let mut iter = op_simplify.into_iter().peekable();
                 let mut out = FastFunction::default();
@@ -840,6 +2197,15 @@ impl From<Function> for FastFunction {
                                         FastMathInstr::Number(c.log() / b)
                                     }
 
+                                    FastMathInstr::Max(c2) => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.max_by_modulus(c2))
+                                    }
+                                    FastMathInstr::Min(c2) => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.min_by_modulus(c2))
+                                    }
+
                                     FastMathInstr::Sin => {
                                         iter.next().unwrap();
                                         FastMathInstr::Number(c.sin())
@@ -866,6 +2232,91 @@ impl From<Function> for FastFunction {
                                         FastMathInstr::Number(c.arctan())
                                     }
 
+                                    FastMathInstr::Sinh => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.sinh())
+                                    }
+
+                                    FastMathInstr::Gamma => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.gamma())
+                                    }
+
+                                    FastMathInstr::Recip => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.reciprocal())
+                                    }
+
+                                    FastMathInstr::Zeta => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.zeta(ZETA_TERMS))
+                                    }
+
+                                    FastMathInstr::Floor => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.floor())
+                                    }
+
+                                    FastMathInstr::Ceil => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.ceil())
+                                    }
+
+                                    FastMathInstr::Sign => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.normalize())
+                                    }
+
+                                    FastMathInstr::Cis => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(Complex::cis(c.real))
+                                    }
+
+                                    FastMathInstr::Abs2 => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(Complex::from_real(c.norm_sq()))
+                                    }
+
+                                    FastMathInstr::Cot => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.cot())
+                                    }
+
+                                    FastMathInstr::Sec => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.sec())
+                                    }
+
+                                    FastMathInstr::Csc => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.csc())
+                                    }
+
+                                    FastMathInstr::Neg => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(-c)
+                                    }
+
+                                    FastMathInstr::Frac => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.frac())
+                                    }
+
+                                    FastMathInstr::Erf => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.erf())
+                                    }
+
+                                    FastMathInstr::Clamp(min, max) => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.clamp_real(min, max))
+                                    }
+
+                                    FastMathInstr::Cbrt => {
+                                        iter.next().unwrap();
+                                        FastMathInstr::Number(c.cbrt())
+                                    }
+
                                     _ => FastMathInstr::Number(c),
                                 }
                             } else {
@@ -881,34 +2332,164 @@ impl From<Function> for FastFunction {
                 out
             };
 
+            // Each folding replaces a number and an operation by a number
+            stats.constants_folded += op_simplify_len - pre_computed.len();
             fast_instr = pre_computed;
         }
 
         // Simplify real numbers
-        for instr in fast_instr.iter_mut() {
-            match instr {
-                FastMathInstr::Add(z) if z.is_real() => {
-                    *instr = FastMathInstr::AddR(z.real);
-                }
-                FastMathInstr::Sub(z) if z.is_real() => {
-                    *instr = FastMathInstr::SubR(z.real);
-                }
-                FastMathInstr::Mul(z) if z.is_real() => {
-                    *instr = FastMathInstr::MulR(z.real);
-                }
-                FastMathInstr::Div(z) if z.is_real() => {
-                    *instr = FastMathInstr::DivR(z.real);
-                }
-                FastMathInstr::Pow(z) if z.is_real() => {
-                    *instr = FastMathInstr::PowR(z.real);
-                }
+        let fast_instr = fast_instr.map(|instr| match instr {
+            FastMathInstr::Add(z) if z.is_real() => FastMathInstr::AddR(z.real),
+            FastMathInstr::Sub(z) if z.is_real() => FastMathInstr::SubR(z.real),
+            FastMathInstr::Mul(z) if z.is_real() => FastMathInstr::MulR(z.real),
+            FastMathInstr::Div(z) if z.is_real() => FastMathInstr::DivR(z.real),
+            FastMathInstr::Pow(z) if z.is_real() => FastMathInstr::PowR(z.real),
+            FastMathInstr::Log(z) if z.is_real() => FastMathInstr::LogR(z.real),
+            instr => instr,
+        });
+
+        debug_assert!(
+            !source_valid || fast_instr.validate().is_ok(),
+            "Optimization broke the stack of a valid function"
+        );
+        debug_assert!(
+            !source_valid || fast_instr.max_stack_depth() < EVAL_STACK_SIZE,
+            "Optimization made a valid function too deep for the stack"
+        );
+        stats.final_len = fast_instr.len();
+        (fast_instr, stats)
+    }
+}
 
-                FastMathInstr::Log(z) if z.is_real() => *instr = FastMathInstr::LogR(z.real),
-                _ => {}
-            }
+// Back to plain instructions, constants are written out and specialized instructions split again
+// Instructions past FUNCTION_SIZE are dropped as constants can take more room than they did before
+// Err when the plain instructions don't fit in FUNCTION_SIZE
+impl TryFrom<FastFunction> for Function {
+    type Error = ();
+
+    fn try_from(func: FastFunction) -> Result<Self, ()> {
+        type Instructions = Vec<MathInstruction, 5>;
+
+        fn seq(instructions: &[MathInstruction]) -> Instructions {
+            Vec::from_slice(instructions).unwrap()
         }
+        fn then(mut instructions: Instructions, instr: MathInstruction) -> Instructions {
+            instructions.push(instr).unwrap();
+            instructions
+        }
+        fn number(c: Complex) -> Instructions {
+            Function::constant(c).iter().cloned().collect()
+        }
+
+        let instructions = func.iter().flat_map(|instr| match instr {
+            FastMathInstr::Z => seq(&[MathInstruction::Z]),
+            FastMathInstr::C => seq(&[MathInstruction::C]),
+            FastMathInstr::UserConst(i) => seq(&[MathInstruction::UserConst(*i)]),
+            FastMathInstr::Number(c) => number(*c),
+
+            FastMathInstr::ConjZ => seq(&[MathInstruction::ConjZ]),
+            FastMathInstr::Conj => seq(&[MathInstruction::Conj]),
+            FastMathInstr::ReZ => seq(&[MathInstruction::Z, MathInstruction::Re]),
+            FastMathInstr::ImZ => seq(&[MathInstruction::Z, MathInstruction::Im]),
+            FastMathInstr::Re => seq(&[MathInstruction::Re]),
+            FastMathInstr::Im => seq(&[MathInstruction::Im]),
+
+            FastMathInstr::Add(c) => then(number(*c), MathInstruction::Add),
+            FastMathInstr::Sub(c) => then(number(*c), MathInstruction::Sub),
+            FastMathInstr::Mul(c) => then(number(*c), MathInstruction::Mul),
+            FastMathInstr::Div(c) => then(number(*c), MathInstruction::Div),
+            FastMathInstr::Pow(c) => then(number(*c), MathInstruction::Pow),
+            // Log keeps the logarithm of its base
+            FastMathInstr::Log(c) => then(number(c.exp()), MathInstruction::Log),
+            FastMathInstr::AddR(x) => seq(&[MathInstruction::Number(*x), MathInstruction::Add]),
+            FastMathInstr::SubR(x) => seq(&[MathInstruction::Number(*x), MathInstruction::Sub]),
+            FastMathInstr::MulR(x) => seq(&[MathInstruction::Number(*x), MathInstruction::Mul]),
+            FastMathInstr::DivR(x) => seq(&[MathInstruction::Number(*x), MathInstruction::Div]),
+            FastMathInstr::PowR(x) => seq(&[MathInstruction::Number(*x), MathInstruction::Pow]),
+            FastMathInstr::LogR(x) => {
+                seq(&[MathInstruction::Number(expf(*x)), MathInstruction::Log])
+            }
+            FastMathInstr::AddZ => seq(&[MathInstruction::Z, MathInstruction::Add]),
+            FastMathInstr::SubZ => seq(&[MathInstruction::Z, MathInstruction::Sub]),
+            FastMathInstr::MulZ => seq(&[MathInstruction::Z, MathInstruction::Mul]),
+            FastMathInstr::DivZ => seq(&[MathInstruction::Z, MathInstruction::Div]),
+            FastMathInstr::PowZ => seq(&[MathInstruction::Z, MathInstruction::Pow]),
+            FastMathInstr::LogZ => seq(&[MathInstruction::Z, MathInstruction::Log]),
+            FastMathInstr::AddS => seq(&[MathInstruction::Add]),
+            FastMathInstr::SubS => seq(&[MathInstruction::Sub]),
+            FastMathInstr::MulS => seq(&[MathInstruction::Mul]),
+            FastMathInstr::DivS => seq(&[MathInstruction::Div]),
+            FastMathInstr::PowS => seq(&[MathInstruction::Pow]),
+            FastMathInstr::LogS => seq(&[MathInstruction::Log]),
+
+            FastMathInstr::ExpZ => seq(&[MathInstruction::Z, MathInstruction::Exp]),
+            FastMathInstr::Exp => seq(&[MathInstruction::Exp]),
+            FastMathInstr::LnZ => seq(&[MathInstruction::Z, MathInstruction::Ln]),
+            FastMathInstr::Ln => seq(&[MathInstruction::Ln]),
+            FastMathInstr::SinZ => seq(&[MathInstruction::Z, MathInstruction::Sin]),
+            FastMathInstr::Sin => seq(&[MathInstruction::Sin]),
+            FastMathInstr::CosZ => seq(&[MathInstruction::Z, MathInstruction::Cos]),
+            FastMathInstr::Cos => seq(&[MathInstruction::Cos]),
+            FastMathInstr::TanZ => seq(&[MathInstruction::Z, MathInstruction::Tan]),
+            FastMathInstr::Tan => seq(&[MathInstruction::Tan]),
+            FastMathInstr::ArcsinZ => seq(&[MathInstruction::Z, MathInstruction::Arcsin]),
+            FastMathInstr::Arcsin => seq(&[MathInstruction::Arcsin]),
+            FastMathInstr::ArccosZ => seq(&[MathInstruction::Z, MathInstruction::Arccos]),
+            FastMathInstr::Arccos => seq(&[MathInstruction::Arccos]),
+            FastMathInstr::ArctanZ => seq(&[MathInstruction::Z, MathInstruction::Arctan]),
+            FastMathInstr::Arctan => seq(&[MathInstruction::Arctan]),
+            FastMathInstr::SinhZ => seq(&[MathInstruction::Z, MathInstruction::Sinh]),
+            FastMathInstr::Sinh => seq(&[MathInstruction::Sinh]),
+            FastMathInstr::GammaZ => seq(&[MathInstruction::Z, MathInstruction::Gamma]),
+            FastMathInstr::Gamma => seq(&[MathInstruction::Gamma]),
+            FastMathInstr::RecipZ => seq(&[MathInstruction::Z, MathInstruction::Recip]),
+            FastMathInstr::Recip => seq(&[MathInstruction::Recip]),
+            FastMathInstr::ZetaZ => seq(&[MathInstruction::Z, MathInstruction::Zeta]),
+            FastMathInstr::Zeta => seq(&[MathInstruction::Zeta]),
+            FastMathInstr::FloorZ => seq(&[MathInstruction::Z, MathInstruction::Floor]),
+            FastMathInstr::Floor => seq(&[MathInstruction::Floor]),
+            FastMathInstr::CeilZ => seq(&[MathInstruction::Z, MathInstruction::Ceil]),
+            FastMathInstr::Ceil => seq(&[MathInstruction::Ceil]),
+            FastMathInstr::SignZ => seq(&[MathInstruction::Z, MathInstruction::Sign]),
+            FastMathInstr::Sign => seq(&[MathInstruction::Sign]),
+            FastMathInstr::CisZ => seq(&[MathInstruction::Z, MathInstruction::Cis]),
+            FastMathInstr::Cis => seq(&[MathInstruction::Cis]),
+            FastMathInstr::Abs2Z => seq(&[MathInstruction::Z, MathInstruction::Abs2]),
+            FastMathInstr::Abs2 => seq(&[MathInstruction::Abs2]),
+            FastMathInstr::CotZ => seq(&[MathInstruction::Z, MathInstruction::Cot]),
+            FastMathInstr::Cot => seq(&[MathInstruction::Cot]),
+            FastMathInstr::SecZ => seq(&[MathInstruction::Z, MathInstruction::Sec]),
+            FastMathInstr::Sec => seq(&[MathInstruction::Sec]),
+            FastMathInstr::CscZ => seq(&[MathInstruction::Z, MathInstruction::Csc]),
+            FastMathInstr::Csc => seq(&[MathInstruction::Csc]),
+            FastMathInstr::NegZ => seq(&[MathInstruction::Z, MathInstruction::Neg]),
+            FastMathInstr::Neg => seq(&[MathInstruction::Neg]),
+            FastMathInstr::FracZ => seq(&[MathInstruction::Z, MathInstruction::Frac]),
+            FastMathInstr::Frac => seq(&[MathInstruction::Frac]),
+
+            FastMathInstr::Over => seq(&[MathInstruction::Over]),
+
+            FastMathInstr::Max(c) => then(number(*c), MathInstruction::Max),
+            FastMathInstr::Min(c) => then(number(*c), MathInstruction::Min),
+            FastMathInstr::MaxS => seq(&[MathInstruction::Max]),
+            FastMathInstr::MinS => seq(&[MathInstruction::Min]),
+
+            FastMathInstr::ErfZ => seq(&[MathInstruction::Z, MathInstruction::Erf]),
+            FastMathInstr::Erf => seq(&[MathInstruction::Erf]),
+
+            // Only made from CLAMP_RANGES
+            FastMathInstr::Clamp(min, max) => {
+                let i = CLAMP_RANGES
+                    .iter()
+                    .position(|range| *range == (*min, *max))
+                    .unwrap();
+                seq(&[MathInstruction::Clamp(i as u8)])
+            }
 
-        fast_instr
+            FastMathInstr::CbrtZ => seq(&[MathInstruction::Z, MathInstruction::Cbrt]),
+            FastMathInstr::Cbrt => seq(&[MathInstruction::Cbrt]),
+        });
+        Function::try_from_iter(instructions).ok_or(())
     }
 }
 
@@ -924,3 +2505,192 @@ impl From<Function> for StringFunction {
         s
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(z: Complex) -> EvalContext {
+        EvalContext {
+            z,
+            c: Complex::ZERO,
+            constants: [Complex::ZERO; USER_CONSTANTS],
+        }
+    }
+
+    #[test]
+    fn conj_z_is_fused() {
+        let func = FunctionBuilder::new().z().conj().build().unwrap();
+        let fast = FastFunction::from(func.clone());
+        assert_eq!(&fast[..], &[FastMathInstr::ConjZ][..]);
+
+        let z = Complex {
+            real: 1.5,
+            imag: -0.25,
+        };
+        assert_eq!(func.eval(&context(z)), z.conj());
+        assert_eq!(fast.eval(&context(z)), z.conj());
+    }
+
+    #[test]
+    fn written_back_from_fast() {
+        let func = Function::from(
+            &[
+                MathInstruction::Z,
+                MathInstruction::Number(2.),
+                MathInstruction::Mul,
+                MathInstruction::Number(1.),
+                MathInstruction::Imag,
+                MathInstruction::Add,
+                MathInstruction::Sin,
+                MathInstruction::Clamp(1),
+            ][..],
+        );
+        let back = Function::try_from(FastFunction::from(func.clone())).unwrap();
+        assert!(back.validate().is_ok());
+        let z = Complex {
+            real: 0.5,
+            imag: 2.,
+        };
+        assert_eq!(back.eval(&context(z)), func.eval(&context(z)));
+    }
+
+    #[test]
+    fn composition_replaces_z() {
+        let square = FunctionBuilder::new().z().z().mul().build().unwrap();
+        let shift = FunctionBuilder::new().z().number(1.).add().build().unwrap();
+        let func = Function::composition(&square, &shift).unwrap();
+        let z = Complex::I;
+        assert_eq!(
+            func.eval(&context(z)),
+            (z + Complex::ONE) * (z + Complex::ONE)
+        );
+
+        // z + z + ... + z, valid but too long once each z takes three instructions
+        let long = (0..100)
+            .fold(FunctionBuilder::new().z(), |b, _| b.z().add())
+            .build()
+            .unwrap();
+        assert!(Function::composition(&long, &shift).is_none());
+    }
+
+    #[test]
+    fn from_iter_past_function_size() {
+        let zs = || (0..FUNCTION_SIZE + 1).map(|_| MathInstruction::Z);
+        assert_eq!(zs().collect::<Function>().len(), FUNCTION_SIZE);
+        assert!(Function::try_from_iter(zs()).is_none());
+        assert_eq!(
+            Function::try_from_iter(zs().skip(1)).map(|func| func.len()),
+            Some(FUNCTION_SIZE)
+        );
+    }
+
+    #[test]
+    fn infix() {
+        let func = Function::from(
+            &[
+                MathInstruction::Z,
+                MathInstruction::Z,
+                MathInstruction::Pow,
+                MathInstruction::Z,
+                MathInstruction::Add,
+            ][..],
+        );
+        assert_eq!(func.to_infix_string().as_str(), "((z^z)+z)");
+
+        // Too long for the string, cut with an ellipsis
+        let long = (1..FUNCTION_SIZE)
+            .fold(FunctionBuilder::new().z(), |b, _| b.clamp(3))
+            .build()
+            .unwrap();
+        let infix = long.to_infix_string();
+        assert!(infix.starts_with("clamp[-inf,0](clamp[-inf,0]("));
+        assert!(infix.ends_with('…'));
+    }
+
+    #[test]
+    fn every_syntax_error() {
+        let func = Function::from(
+            &[
+                MathInstruction::Add,
+                MathInstruction::Mul,
+                MathInstruction::Z,
+                MathInstruction::Z,
+            ][..],
+        );
+        let errors = func.validate_with_errors().unwrap_err();
+        assert_eq!(errors.first.op_index, 0);
+        assert!(matches!(errors.first.kind, SyntaxErrorKind::Underflow));
+        assert_eq!(&errors.op_indices[..], &[0, 1][..]);
+        assert!(errors.not_singleton);
+    }
+
+    fn round_trip(func: &Function) -> Option<Function> {
+        Function::from_bytes(&func.to_bytes::<64>()?)
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let funcs = [
+            Function::identity(),
+            FunctionBuilder::new().z().z().pow().build().unwrap(),
+            FunctionBuilder::new()
+                .number(-1.5e-3)
+                .z()
+                .mul()
+                .clamp(2)
+                .user_const(3)
+                .add()
+                .build()
+                .unwrap(),
+            FunctionBuilder::new().number(f32::NAN).build().unwrap(),
+        ];
+        for func in funcs.iter() {
+            assert_eq!(round_trip(func).as_ref(), Some(func));
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let func = FunctionBuilder::new().z().number(2.).pow().sin().build();
+        let expected = [
+            MathInstruction::Z,
+            MathInstruction::Number(2.),
+            MathInstruction::Pow,
+            MathInstruction::Sin,
+        ];
+        assert_eq!(func.unwrap(), Function::from(&expected[..]));
+
+        match FunctionBuilder::new().z().add().build() {
+            Err(BuildError::Syntax(err)) => assert_eq!(err.op_index, 1),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+        let too_long = (0..FUNCTION_SIZE).fold(FunctionBuilder::new().z(), |b, _| b.neg());
+        assert!(matches!(too_long.build(), Err(BuildError::TooLong)));
+    }
+
+    #[test]
+    fn numbers_compare_by_bits() {
+        let number = |x: f32| Function::from(&[MathInstruction::Number(x)][..]);
+        assert_eq!(number(f32::NAN), number(f32::NAN));
+        assert_ne!(number(0.), number(-0.));
+        assert_ne!(MathInstruction::Clamp(0), MathInstruction::Clamp(1));
+        assert_ne!(MathInstruction::Z, MathInstruction::C);
+    }
+
+    #[test]
+    fn to_bytes_too_small() {
+        let func = Function::from(&[MathInstruction::Number(2.)][..]);
+        assert!(func.to_bytes::<4>().is_none());
+        assert_eq!(func.to_bytes::<5>().map(|bytes| bytes.len()), Some(5));
+    }
+
+    #[test]
+    fn from_bytes_rejects_invalid() {
+        // Unknown opcode, cut number, clamp range out of the table, stack left empty
+        assert!(Function::from_bytes(&[255]).is_none());
+        assert!(Function::from_bytes(&[1, 0, 0]).is_none());
+        assert!(Function::from_bytes(&[0, 47, 4]).is_none());
+        assert!(Function::from_bytes(&[]).is_none());
+    }
+}
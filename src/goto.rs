@@ -3,20 +3,35 @@ use core::fmt::Write;
 use heapless::String;
 
 use crate::eadk::{
-    display::{self, SCREEN_WIDTH},
+    display::{self, SCREEN_HEIGHT, SCREEN_WIDTH},
     key, keyboard, timing, Color, Point, Rect,
 };
 
-use crate::plot::{plot_func, plot_rect};
-use crate::utils::keyboard_number;
+use crate::complex::{Complex, ComplexRect};
+
+use crate::plot::{plot_func, plot_preview, plot_rect};
 use crate::utils::CHARACTER_HEIGHT;
+use crate::utils::{keyboard_angle, keyboard_complex, keyboard_number, number_or_cursor};
+use crate::utils::{wait_till_released, wait_till_released_timeout, RELEASE_TIMEOUT_MS};
 
 use crate::State;
 
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum InputMode {
+    Coordinates,
+    Complex,
+    Polar,
+}
+
 pub fn goto(state: &mut State) {
+    let mut mode = InputMode::Coordinates;
     let mut x: String<20> = String::new();
     let mut y: String<20> = String::new();
-    let mut y_selected = false;
+    let mut z: String<40> = String::new();
+    let mut r: String<20> = String::new();
+    let mut theta: String<20> = String::new();
+    let mut second_selected = false;
+    let mut first_value = 0.;
 
     let x_margin = (state.area.to_real - state.area.from_real) / 2.;
     let y_margin = (state.area.to_imag - state.area.from_imag) / 2.;
@@ -32,11 +47,30 @@ pub fn goto(state: &mut State) {
             Color::WHITE,
         );
 
-        let mut pos_str: String<50> = String::new();
-        write!(&mut pos_str, "x = {}\ny = {}\0", x, y).unwrap();
+        let mut pos_str: String<64> = String::new();
+        match mode {
+            InputMode::Coordinates => write!(
+                &mut pos_str,
+                "x = {}\ny = {}\0",
+                number_or_cursor(&x),
+                number_or_cursor(&y)
+            ),
+            InputMode::Complex => write!(&mut pos_str, "z = {}\0", number_or_cursor(&z)),
+            InputMode::Polar => write!(
+                &mut pos_str,
+                "(r, θ) r = {}\n       θ = {}\0",
+                number_or_cursor(&r),
+                number_or_cursor(&theta)
+            ),
+        }
+        .unwrap();
         display::draw_string(&pos_str, Point::ZERO, false, Color::BLACK, Color::WHITE);
 
-        if keyboard::scan().key_down(key::BACK) {
+        let keyboard_state = keyboard::scan();
+        let mut destination = None;
+
+        if keyboard_state.key_down(key::BACK) {
+            wait_till_released(key::BACK);
             plot_rect(
                 state,
                 Rect {
@@ -45,20 +79,55 @@ pub fn goto(state: &mut State) {
                     width: SCREEN_WIDTH,
                     height: CHARACTER_HEIGHT * 2,
                 },
+                0,
+                1,
+                0,
             );
-            break;
-        }
-
-        if !y_selected {
+            return;
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::EXE) {
+            mode = match mode {
+                InputMode::Coordinates | InputMode::Polar => InputMode::Complex,
+                InputMode::Complex => InputMode::Coordinates,
+            };
+            second_selected = false;
+            wait_till_released_timeout(key::EXE, RELEASE_TIMEOUT_MS);
+        } else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::SHIFT) {
+            mode = match mode {
+                InputMode::Coordinates | InputMode::Complex => InputMode::Polar,
+                InputMode::Polar => InputMode::Coordinates,
+            };
+            second_selected = false;
+            wait_till_released(key::SHIFT);
+        } else if mode == InputMode::Polar {
+            if !second_selected {
+                if let Some(num) = keyboard_number(&mut r) {
+                    first_value = num;
+                    second_selected = true;
+                }
+            } else if let Some(argument) = keyboard_angle(&mut theta) {
+                destination = Some(Complex::from_polar(first_value, argument));
+            }
+        } else if mode == InputMode::Complex {
+            destination = keyboard_complex(&mut z);
+        } else if !second_selected {
             if let Some(num) = keyboard_number(&mut x) {
-                state.area.from_real = num - x_margin;
-                state.area.to_real = num + x_margin;
-                y_selected = true;
+                first_value = num;
+                second_selected = true;
             }
         } else if let Some(num) = keyboard_number(&mut y) {
-            state.area.from_imag = num - y_margin;
-            state.area.to_imag = num + y_margin;
-            break;
+            destination = Some(Complex {
+                real: first_value,
+                imag: num,
+            });
+        }
+
+        if let Some(c) = destination {
+            let target = ComplexRect::from_center(c, x_margin, y_margin);
+
+            if preview(state, &target) {
+                state.navigate(target);
+                break;
+            }
         }
 
         timing::msleep(100);
@@ -67,3 +136,43 @@ pub fn goto(state: &mut State) {
 
     plot_func(state);
 }
+
+// Show the target area in the bottom half of the screen, return whether it was confirmed
+fn preview(state: &State, target: &ComplexRect) -> bool {
+    plot_preview(
+        state,
+        target,
+        Rect {
+            x: SCREEN_WIDTH / 4,
+            y: SCREEN_HEIGHT / 2,
+            width: SCREEN_WIDTH / 2,
+            height: SCREEN_HEIGHT / 2,
+        },
+    );
+
+    loop {
+        let keyboard_state = keyboard::scan();
+
+        if keyboard_state.key_down(key::EXE) {
+            wait_till_released_timeout(key::EXE, RELEASE_TIMEOUT_MS);
+            return true;
+        } else if keyboard_state.key_down(key::BACK) {
+            wait_till_released(key::BACK);
+            plot_rect(
+                state,
+                Rect {
+                    x: 0,
+                    y: SCREEN_HEIGHT / 2,
+                    width: SCREEN_WIDTH,
+                    height: SCREEN_HEIGHT / 2,
+                },
+                0,
+                1,
+                0,
+            );
+            return false;
+        }
+
+        timing::msleep(100);
+    }
+}
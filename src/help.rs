@@ -0,0 +1,167 @@
+use core::fmt::Write;
+
+use heapless::String;
+
+use crate::eadk::display::{self, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::eadk::{key, keyboard, timing, Color, Point, Rect};
+
+use crate::plot::plot_func;
+use crate::utils::{wait_till_released, CHARACTER_HEIGHT};
+
+use crate::State;
+
+const LINES_BY_SCREEN: usize = (SCREEN_HEIGHT / CHARACTER_HEIGHT) as usize;
+
+const HELP_PAGES: [&str; 4] = [
+    "Arrows: move around
++ / -: zoom in / out
+BACK: previous view
+SHIFT + BACK: next view
+1 to 4: restore a bookmark
+SHIFT + 1 to 4: bookmark the view, kept on exit
+SHIFT + ALPHA + 1 to 3: function slot A to C
+ALPHA + V: split screen with another slot
+ALPHA + E: equal axes
+SHIFT + ,: keep axes equal
+ALPHA + G: go to a value
+SHIFT + ALPHA + G: round the bounds
+ALPHA + B: show / hide the status bar
+ALPHA + 0 or SHIFT + HOME: this help
+HOME: exit",
+    "ALPHA + S: sigmoid style
+ALPHA + R: checkerboard style
+ALPHA + T: log2 style
+ALPHA + D: modulus bands style
+SHIFT + UP / DOWN: number of bands
+ALPHA + H: gradient style
+ALPHA + U: colorblind friendly style
+(SHIFT) + ALPHA + LEFT / RIGHT: its first (last) hue
+SHIFT + LEFT / RIGHT: saturation
+ALPHA + M: Mandelbrot set
+ALPHA + J: Julia set
+ALPHA + N: Newton fractal of f
+ALPHA + P: phase lines
+ALPHA + Q: contours of |f(z)|
+ALPHA + Z: mark poles and zeros
+ALPHA + O: f(g(z)), g of the next slot
+ALPHA + X: show / hide the axes
+ALPHA + L: show / hide the progress bar
+ALPHA + C: set the parameter c
+ALPHA + A: animate c along its path
+SHIFT + ALPHA + TOOLBOX: set the constants A to D",
+    "TOOLBOX: edit the function (RPN)
+  Digits then EXE: real number
+  i: multiply by i
+  ALPHA + -: conjugate
+  SHIFT + -: negate
+  ALPHA + XNT: conjugate of z
+  ALPHA + R / ALPHA + I: Re / Im
+  SHIFT + EXP: e
+  ALPHA + C: parameter c
+  SHIFT + ALPHA + EXP/LN/LOG/i: constants A to D
+  SHIFT + π / ALPHA + π: 2π / π/2
+  SHIFT + SIN/COS/TAN: arcsin/arccos/arctan
+  ALPHA + SIN: sinh
+  SHIFT + ALPHA + SIN/COS/TAN: csc/sec/cot
+  ALPHA + !: gamma
+  SHIFT + ÷: reciprocal
+  ALPHA + +: zeta
+  ALPHA + EXP: erf, slow
+  ALPHA + SQRT: clamp Re(z), again for other bounds
+  SHIFT + SQRT: cube root
+  ALPHA / SHIFT + ^: floor / ceil
+  SHIFT + .: fractional part
+  ALPHA + O: over, copy the second value
+  ALPHA / SHIFT + ALPHA + M: max / min by |z|
+  ALPHA + S: sign z/|z|
+  SHIFT + i: cis, e^(i Re(z))
+  SHIFT + x^2: |z|^2
+  SHIFT + LN / LOG: log2 / log10
+  ALPHA + VAR: show as a formula
+  OK: validate, BACK: cancel",
+    "VAR: values mode
+  Arrows: move the cursor
+  SHIFT: show / hide f'(z)
+  EXE: start then end of the c animation
+  In Julia mode, the cursor sets c
+  BACK: leave
+
+Go to mode
+  ALPHA + EXE: complex input
+  ALPHA + SHIFT: polar input
+  EXE: confirm the preview",
+];
+
+// Each page scrolls with UP and DOWN when it's longer than the screen
+pub fn show_help(state: &State) {
+    let mut page_index = 0;
+    let mut first_line = 0;
+
+    while keyboard::scan().any_down() {}
+
+    loop {
+        let text = HELP_PAGES[page_index];
+        let line_count = text.lines().count();
+
+        let mut page: String<1024> = String::new();
+        writeln!(
+            &mut page,
+            "Help {}/{}  LEFT/RIGHT: page, BACK: leave",
+            page_index + 1,
+            HELP_PAGES.len()
+        )
+        .unwrap();
+        text.lines()
+            .skip(first_line)
+            .take(LINES_BY_SCREEN - 1)
+            .for_each(|line| {
+                page.push_str(line).unwrap();
+                page.push('\n').unwrap();
+            });
+        page.push('\0').unwrap();
+
+        display::push_rect_uniform(
+            Rect {
+                x: 0,
+                y: 0,
+                width: SCREEN_WIDTH,
+                height: SCREEN_HEIGHT,
+            },
+            Color::WHITE,
+        );
+        display::draw_string(&page, Point::ZERO, false, Color::BLACK, Color::WHITE);
+
+        loop {
+            let keyboard_state = keyboard::scan();
+
+            if keyboard_state.key_down(key::LEFT) && page_index > 0 {
+                page_index -= 1;
+                first_line = 0;
+                wait_till_released(key::LEFT);
+                break;
+            } else if keyboard_state.key_down(key::RIGHT) && page_index + 1 < HELP_PAGES.len() {
+                page_index += 1;
+                first_line = 0;
+                wait_till_released(key::RIGHT);
+                break;
+            } else if keyboard_state.key_down(key::UP) && first_line > 0 {
+                first_line -= 1;
+                break;
+            } else if keyboard_state.key_down(key::DOWN)
+                && first_line + LINES_BY_SCREEN - 1 < line_count
+            {
+                first_line += 1;
+                break;
+            } else if keyboard_state.key_down(key::BACK) {
+                wait_till_released(key::BACK);
+                plot_func(state);
+                return;
+            }
+
+            timing::msleep(20);
+        }
+
+        timing::msleep(100);
+        display::wait_for_vblank();
+    }
+}
@@ -1,25 +1,39 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(test), no_main)]
 pub mod eadk;
 
-use eadk::{
-    display::{SCREEN_HEIGHT, SCREEN_WIDTH},
-    key, keyboard,
-};
+use core::cell::Cell;
+
+use heapless::Vec;
+
+use eadk::{display, key, keyboard, timing};
 
 mod complex;
-use complex::ComplexRect;
+use complex::{Complex, ComplexRect, MIN_RECT_EXTENT};
 
 mod function;
-use function::{FastFunction, Function, MathInstruction};
+use function::{EvalContext, Evaluate, FastFunction, Function, Validate, USER_CONSTANTS};
 
 mod plot;
 mod utils;
-use plot::complex_to_color::ColorMapper;
-use plot::plot_func;
+use plot::complex_to_color::{wrap_hue, ColorMapper, ColorSettings, DEFAULT_GRADIENT, HUE_STEP};
+use plot::complex_to_color::{MAX_BANDS, MIN_BANDS, MIN_SATURATION};
+use plot::{clear_status_bar, draw_bookmark_indicator, draw_status_bar, draw_warning};
+use plot::{plot_func, plot_func_progressive, plot_pan, plot_transition};
+use plot::{RenderMode, RenderTimes, ViewMode};
+use utils::{enforce_screen_aspect, wait_till_released};
+use utils::{Debouncer, KeyRepeat, KEY_REPEAT_DELAY, KEY_REPEAT_INTERVAL};
 
+mod constants;
 mod editor;
+mod fractal;
 mod goto;
+mod help;
+mod parameter;
+mod settings;
+use settings::{Settings, SETTINGS_RECORD};
+mod storage;
+use storage::{StorageSlot, BOOKMARK_SIZE, FUNCTION_RECORD_SIZE};
 mod values;
 
 #[used]
@@ -34,111 +48,575 @@ pub static EADK_APP_API_LEVEL: u32 = 0;
 #[link_section = ".rodata.eadk_app_icon"]
 pub static EADK_APP_ICON: [u8; 3477] = *include_bytes!("../target/icon.nwi");
 
+const BOOKMARK_KEYS: [u32; 4] = [key::ONE, key::TWO, key::THREE, key::FOUR];
+const HISTORY_SIZE: usize = 16;
+// Recalled with SHIFT + ALPHA and the first bookmark keys
+const FUNCTION_SLOTS: usize = 3;
+const SLOT_RECORDS: [StorageSlot<FUNCTION_RECORD_SIZE>; FUNCTION_SLOTS] = [
+    StorageSlot::new("complex_slot_a\0"),
+    StorageSlot::new("complex_slot_b\0"),
+    StorageSlot::new("complex_slot_c\0"),
+];
+const BOOKMARKS_RECORD: StorageSlot<{ BOOKMARK_KEYS.len() * BOOKMARK_SIZE }> =
+    StorageSlot::new("complex_bookmarks\0");
+const CONSTANTS_RECORD: StorageSlot<{ USER_CONSTANTS * 8 }> =
+    StorageSlot::new("complex_constants\0");
+const FRACTAL_ITERATIONS: u32 = 64;
+const NEWTON_ITERATIONS: u32 = 32;
+const NEWTON_TOLERANCE: f32 = 1e-3;
+const PHASE_LINES: u8 = 8;
+const ANIMATION_FRAMES: u16 = 20;
+const DEFAULT_BANDS: u8 = 4;
+const DEFAULT_JULIA_PARAMETER: Complex = Complex {
+    real: -0.8,
+    imag: 0.156,
+};
+
 pub struct State {
     func: FastFunction,
     func_body: Function,
     area: ComplexRect,
-    color_mode: ColorMapper,
+    color: ColorSettings,
+    render_mode: RenderMode,
+    bookmarks: [Option<ComplexRect>; BOOKMARK_KEYS.len()],
+    history: Vec<ComplexRect, HISTORY_SIZE>,
+    forward_history: Vec<ComplexRect, HISTORY_SIZE>,
+    show_status: bool,
+    // Value of the C variable in the function
+    parameter: Complex,
+    // Values of the A to D constants
+    constants: [Complex; USER_CONSTANTS],
+    // Segment the parameter sweeps when animated, picked in values mode
+    c_start: Complex,
+    c_end: Complex,
+    // Number of phase lines drawn over the plot
+    phase_lines: Option<u8>,
+    // Lines of constant |f(z)|
+    show_contours: bool,
+    // Markers on the poles and zeros found on a coarse grid
+    show_poles_zeros: bool,
+    // Keep both axes at the same scale whenever the view changes
+    aspect_locked: bool,
+    // Draw the real and imaginary axes over the plot
+    show_axes: bool,
+    // A bar along the bottom row grows as full renders go
+    show_progress: bool,
+    // Show how long full renders take, hidden
+    debug_timing: bool,
+    render_times: Cell<RenderTimes>,
+    // Functions kept aside, the current one lives in func_body and is copied back when leaving it
+    func_slots: [Option<Function>; FUNCTION_SLOTS],
+    current_slot: usize,
+    view_mode: ViewMode,
+}
+
+impl State {
+    // Move to a new area, remembering the current one
+    fn navigate(&mut self, mut area: ComplexRect) {
+        if self.aspect_locked {
+            enforce_screen_aspect(&mut area);
+        }
+        // Staying in place would add a BACK step going nowhere
+        if area == self.area {
+            return;
+        }
+
+        if self.history.is_full() {
+            self.history.remove(0);
+        }
+        self.history.push(self.area).unwrap();
+        self.forward_history.clear();
+        self.area = area;
+    }
+
+    // Keep the current function in its slot, then recall the other slot or start it from a copy
+    fn switch_slot(&mut self, slot: usize) {
+        if slot == self.current_slot {
+            return;
+        }
+
+        self.func_slots[self.current_slot] = Some(self.func_body.clone());
+        SLOT_RECORDS[self.current_slot].save(&self.func_body);
+
+        match &self.func_slots[slot] {
+            Some(func_body) => {
+                self.func_body = func_body.clone();
+                self.func = FastFunction::from(func_body.clone());
+            }
+            None => {
+                self.func_slots[slot] = Some(self.func_body.clone());
+                SLOT_RECORDS[slot].save(&self.func_body);
+            }
+        }
+        self.current_slot = slot;
+    }
+
+    // The part of the state kept for the next run
+    fn settings(&self) -> Settings {
+        Settings {
+            color_mode: self.color.mapper,
+            show_axes: self.show_axes,
+            saturation: self.color.saturation,
+            last_function_slot: self.current_slot as u8,
+        }
+    }
+
+    // Evaluate the function with the current parameter
+    fn eval(&self, z: Complex) -> Complex {
+        self.eval_with(&self.func, z)
+    }
+
+    // Same for another function, such as the one of another slot
+    fn eval_with(&self, func: &FastFunction, z: Complex) -> Complex {
+        func.eval(&EvalContext {
+            z,
+            c: self.parameter,
+            constants: self.constants,
+        })
+    }
 }
 
-#[no_mangle]
+#[cfg_attr(not(test), no_mangle)]
 pub fn main() {
     let mut state = {
-        let func_body = Function::from([MathInstruction::Z].as_slice());
+        // Records that don't hold a valid function anymore are ignored
+        let func_slots = SLOT_RECORDS.each_ref().map(|record| {
+            record
+                .load::<Function>()
+                .filter(|func| func.validate().is_ok())
+        });
+        let settings = SETTINGS_RECORD.load::<Settings>().unwrap_or_default();
+        let current_slot = (settings.last_function_slot as usize).min(FUNCTION_SLOTS - 1);
+        let func_body = func_slots[current_slot]
+            .clone()
+            .unwrap_or_else(Function::identity);
+        // Equal axes fit the screen better than a square
+        let mut area = ComplexRect::standard();
+        enforce_screen_aspect(&mut area);
         State {
             func: FastFunction::from(func_body.clone()),
             func_body,
-            area: ComplexRect {
-                from_real: -10.,
-                to_real: 10.,
-                from_imag: -7.5,
-                to_imag: 7.5,
+            area,
+            color: ColorSettings {
+                mapper: settings.color_mode,
+                saturation: settings.saturation,
             },
-            color_mode: ColorMapper::Sigmoid,
+            render_mode: RenderMode::DomainColoring,
+            bookmarks: BOOKMARKS_RECORD
+                .load()
+                .unwrap_or([None; BOOKMARK_KEYS.len()]),
+            history: Vec::new(),
+            forward_history: Vec::new(),
+            show_status: false,
+            parameter: Complex::ZERO,
+            constants: CONSTANTS_RECORD
+                .load()
+                .unwrap_or([Complex::ZERO; USER_CONSTANTS]),
+            c_start: Complex::ZERO,
+            c_end: Complex::ONE,
+            phase_lines: None,
+            show_contours: false,
+            show_progress: false,
+            show_poles_zeros: false,
+            aspect_locked: false,
+            show_axes: settings.show_axes,
+            debug_timing: false,
+            render_times: Cell::new(RenderTimes::default()),
+            func_slots,
+            current_slot,
+            view_mode: ViewMode::Single,
         }
     };
 
     plot_func(&state);
 
+    // Zoom and pan keys, in the order they are destructured below
+    let mut repeats = [
+        key::PLUS,
+        key::MINUS,
+        key::LEFT,
+        key::RIGHT,
+        key::DOWN,
+        key::UP,
+    ]
+    .map(|k| KeyRepeat::new(k, KEY_REPEAT_DELAY, KEY_REPEAT_INTERVAL));
+
+    // One-shot actions fire when their key goes down, not while it's held
+    let mut debouncer = Debouncer::new(keyboard::scan());
+
+    // Set when a coarse render was interrupted and still needs the full one
+    let mut refine = false;
+
     loop {
         let keyboard_state = keyboard::scan();
+        debouncer.update(keyboard_state);
+        let [zoom_in, zoom_out, left, right, down, up] = repeats
+            .each_mut()
+            .map(|repeat| repeat.pressed(keyboard_state));
+        let bookmark_slot = BOOKMARK_KEYS
+            .iter()
+            .position(|k| keyboard_state.key_down(*k));
 
-        if keyboard_state.key_down(key::HOME) {
+        // Help, before HOME alone exits
+        if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::HOME) {
+            help::show_help(&state);
+        } else if keyboard_state.key_down(key::HOME) {
+            SLOT_RECORDS[state.current_slot].save(&state.func_body);
+            SETTINGS_RECORD.save(&state.settings());
+            CONSTANTS_RECORD.save(&state.constants);
+            BOOKMARKS_RECORD.save(&state.bookmarks);
             break;
-        } else if keyboard_state.key_down(key::PLUS) {
-            state.area.from_real /= 2.;
-            state.area.to_real /= 2.;
-            state.area.from_imag /= 2.;
-            state.area.to_imag /= 2.;
+        }
+        // Function slots, before the axes and bookmarks sharing their keys
+        else if let Some(slot) = bookmark_slot.filter(|slot| {
+            *slot < FUNCTION_SLOTS
+                && keyboard_state.key_down(key::SHIFT)
+                && keyboard_state.key_down(key::ALPHA)
+        }) {
+            if debouncer.just_pressed(BOOKMARK_KEYS[slot]) && slot != state.current_slot {
+                state.switch_slot(slot);
 
-            plot_func(&state);
-        } else if keyboard_state.key_down(key::MINUS) {
-            state.area.from_real *= 2.;
-            state.area.to_real *= 2.;
-            state.area.from_imag *= 2.;
-            state.area.to_imag *= 2.;
+                plot_func(&state);
+            }
+        }
+        // History
+        else if keyboard_state.key_down(key::SHIFT) && keyboard_state.key_down(key::BACK) {
+            if let Some(area) = state.forward_history.pop() {
+                state.history.push(state.area).unwrap();
+                state.area = area;
 
-            plot_func(&state);
-        } else if keyboard_state.key_down(key::LEFT) {
-            let shift = (state.area.to_real - state.area.from_real) / 5.;
-            state.area.from_real -= shift;
-            state.area.to_real -= shift;
+                plot_func(&state);
+            }
+            wait_till_released(key::BACK);
+        } else if keyboard_state.key_down(key::BACK) {
+            if let Some(area) = state.history.pop() {
+                state.forward_history.push(state.area).unwrap();
+                state.area = area;
 
-            plot_func(&state);
-        } else if keyboard_state.key_down(key::RIGHT) {
-            let shift = (state.area.to_real - state.area.from_real) / 5.;
-            state.area.from_real += shift;
-            state.area.to_real += shift;
+                plot_func(&state);
+            }
+            wait_till_released(key::BACK);
+        }
+        // Gradient hues, before the saturation and panning sharing their keys
+        else if keyboard_state.key_down(key::ALPHA)
+            && (left || right)
+            && matches!(state.color.mapper, ColorMapper::Gradient { .. })
+        {
+            if let ColorMapper::Gradient { hue_a, hue_b } = &mut state.color.mapper {
+                let hue = if keyboard_state.key_down(key::SHIFT) {
+                    hue_b
+                } else {
+                    hue_a
+                };
+                let step = if right { HUE_STEP } else { -HUE_STEP };
+                *hue = wrap_hue(*hue + step);
+            }
 
             plot_func(&state);
-        } else if keyboard_state.key_down(key::DOWN) {
-            let shift = (state.area.to_imag - state.area.from_imag) / 5.;
-            state.area.from_imag -= shift;
-            state.area.to_imag -= shift;
+        }
+        // Saturation
+        else if keyboard_state.key_down(key::SHIFT) && (left || right) {
+            let step = if right { 0.1 } else { -0.1 };
+            state.color.saturation = (state.color.saturation + step).clamp(MIN_SATURATION, 1.);
 
             plot_func(&state);
-        } else if keyboard_state.key_down(key::UP) {
-            let shift = (state.area.to_imag - state.area.from_imag) / 5.;
-            state.area.from_imag += shift;
-            state.area.to_imag += shift;
+        }
+        // Number of bands
+        else if keyboard_state.key_down(key::SHIFT)
+            && (up || down)
+            && matches!(state.color.mapper, ColorMapper::DiscreteModulus { .. })
+        {
+            if let ColorMapper::DiscreteModulus { n_bands } = &mut state.color.mapper {
+                *n_bands = if up {
+                    (*n_bands + 1).min(MAX_BANDS)
+                } else {
+                    (*n_bands - 1).max(MIN_BANDS)
+                };
+            }
 
             plot_func(&state);
         }
-        // Equal axes
-        else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::COMMA) {
-            let shift = (state.area.to_real - state.area.from_real)
-                * (SCREEN_HEIGHT as f32 / SCREEN_WIDTH as f32)
-                / 2.;
-            let mean = (state.area.to_imag + state.area.from_imag) / 2.;
+        // Poles and zeros, before + alone zooms in
+        else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::PLUS) {
+            if debouncer.just_pressed(key::PLUS) {
+                state.show_poles_zeros = !state.show_poles_zeros;
 
-            state.area.from_imag = mean - shift;
-            state.area.to_imag = mean + shift;
+                plot_func(&state);
+            }
+        } else if zoom_in {
+            let mut area = state.area;
+            area.from_real /= 2.;
+            area.to_real /= 2.;
+            area.from_imag /= 2.;
+            area.to_imag /= 2.;
 
-            plot_func(&state);
+            if area.is_valid()
+                && area.to_real - area.from_real >= MIN_RECT_EXTENT
+                && area.to_imag - area.from_imag >= MIN_RECT_EXTENT
+            {
+                state.navigate(area);
+                refine = !plot_func_progressive(&state);
+            } else {
+                draw_warning("Can't zoom in further\0");
+            }
+        } else if zoom_out {
+            let mut area = state.area;
+            area.from_real *= 2.;
+            area.to_real *= 2.;
+            area.from_imag *= 2.;
+            area.to_imag *= 2.;
+
+            if area.is_valid() {
+                state.navigate(area);
+                refine = !plot_func_progressive(&state);
+            } else {
+                draw_warning("Can't zoom out further\0");
+            }
+        } else if left || right || down || up {
+            let previous = state.area;
+            let shift_real = (state.area.to_real - state.area.from_real) / 5.;
+            let shift_imag = (state.area.to_imag - state.area.from_imag) / 5.;
+            let delta = if left {
+                Complex::from_real(-shift_real)
+            } else if right {
+                Complex::from_real(shift_real)
+            } else if down {
+                Complex::from_imag(-shift_imag)
+            } else {
+                Complex::from_imag(shift_imag)
+            };
+            state.navigate(state.area.pan(delta));
+
+            // A screen still waiting for its full render can't be reused
+            refine = !if refine {
+                plot_func_progressive(&state)
+            } else {
+                plot_pan(&state, &previous)
+            };
+        }
+        // Equal axes
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::COMMA) {
+            let mut area = state.area;
+            enforce_screen_aspect(&mut area);
+            if area != state.area {
+                state.navigate(area);
+                plot_func(&state);
+            }
+        } else if keyboard_state.key_down(key::SHIFT) && debouncer.just_pressed(key::COMMA) {
+            state.aspect_locked = !state.aspect_locked;
+            let mut area = state.area;
+            enforce_screen_aspect(&mut area);
+            if state.aspect_locked && area != state.area {
+                state.navigate(area);
+                plot_func(&state);
+            }
         }
         // Style
         else if keyboard_state.key_down(key::ALPHA)
-            && keyboard_state.key_down(key::FIVE)
-            && state.color_mode != ColorMapper::Sigmoid
+            && debouncer.just_pressed(key::FIVE)
+            && state.color.mapper != ColorMapper::Sigmoid
+        {
+            state.color.mapper = ColorMapper::Sigmoid;
+
+            plot_func(&state);
+        } else if keyboard_state.key_down(key::ALPHA)
+            && debouncer.just_pressed(key::FOUR)
+            && state.color.mapper != ColorMapper::Checkerboard
+        {
+            state.color.mapper = ColorMapper::Checkerboard;
+
+            plot_func(&state);
+        } else if keyboard_state.key_down(key::ALPHA)
+            && debouncer.just_pressed(key::SIX)
+            && state.color.mapper != ColorMapper::Log2
         {
-            state.color_mode = ColorMapper::Sigmoid;
+            state.color.mapper = ColorMapper::Log2;
 
             plot_func(&state);
         } else if keyboard_state.key_down(key::ALPHA)
-            && keyboard_state.key_down(key::FOUR)
-            && state.color_mode != ColorMapper::Checkerboard
+            && debouncer.just_pressed(key::IMAGINARY)
+            && !matches!(state.color.mapper, ColorMapper::DiscreteModulus { .. })
         {
-            state.color_mode = ColorMapper::Checkerboard;
+            state.color.mapper = ColorMapper::DiscreteModulus {
+                n_bands: DEFAULT_BANDS,
+            };
 
             plot_func(&state);
         } else if keyboard_state.key_down(key::ALPHA)
-            && keyboard_state.key_down(key::SIX)
-            && state.color_mode != ColorMapper::Log2
+            && debouncer.just_pressed(key::COSINE)
+            && !matches!(state.color.mapper, ColorMapper::Gradient { .. })
+        {
+            state.color.mapper = DEFAULT_GRADIENT;
+
+            plot_func(&state);
+        } else if keyboard_state.key_down(key::ALPHA)
+            && debouncer.just_pressed(key::MULTIPLICATION)
+            && state.color.mapper != ColorMapper::Accessible
+        {
+            state.color.mapper = ColorMapper::Accessible;
+
+            plot_func(&state);
+        }
+        // Fractals
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::SEVEN) {
+            state.render_mode = match state.render_mode {
+                RenderMode::Mandelbrot { .. } => RenderMode::DomainColoring,
+                _ => RenderMode::Mandelbrot {
+                    max_iter: FRACTAL_ITERATIONS,
+                },
+            };
+
+            plot_func(&state);
+        } else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::PI) {
+            state.render_mode = match state.render_mode {
+                RenderMode::Julia { .. } => RenderMode::DomainColoring,
+                _ => RenderMode::Julia {
+                    c: DEFAULT_JULIA_PARAMETER,
+                    max_iter: FRACTAL_ITERATIONS,
+                },
+            };
+
+            plot_func(&state);
+        } else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::EIGHT) {
+            state.render_mode = match state.render_mode {
+                RenderMode::Newton { .. } => RenderMode::DomainColoring,
+                _ => RenderMode::Newton {
+                    max_iter: NEWTON_ITERATIONS,
+                    eps: NEWTON_TOLERANCE,
+                },
+            };
+
+            plot_func(&state);
+        }
+        // Phase lines
+        else if keyboard_state.key_down(key::ALPHA)
+            && debouncer.just_pressed(key::LEFTPARENTHESIS)
         {
-            state.color_mode = ColorMapper::Log2;
+            state.phase_lines = match state.phase_lines {
+                Some(_) => None,
+                None => Some(PHASE_LINES),
+            };
+
+            plot_func(&state);
+        }
+        // Contours
+        else if keyboard_state.key_down(key::ALPHA)
+            && debouncer.just_pressed(key::RIGHTPARENTHESIS)
+        {
+            state.show_contours = !state.show_contours;
+
+            plot_func(&state);
+        }
+        // Progress bar, seen from the next render on
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::SQUARE) {
+            state.show_progress = !state.show_progress;
+        }
+        // Axes
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::TWO) {
+            state.show_axes = !state.show_axes;
 
             plot_func(&state);
         }
+        // Split screen, the current slot on the left and the next stored one on the right
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::DIVISION) {
+            match state.view_mode {
+                ViewMode::SplitScreen { .. } => {
+                    state.view_mode = ViewMode::Single;
+
+                    plot_func(&state);
+                }
+                ViewMode::Single => {
+                    let other_slot = (1..FUNCTION_SLOTS)
+                        .map(|n| (state.current_slot + n) % FUNCTION_SLOTS)
+                        .find(|slot| state.func_slots[*slot].is_some());
+                    if let Some(slot_b) = other_slot {
+                        state.view_mode = ViewMode::SplitScreen {
+                            slot_a: state.current_slot,
+                            slot_b,
+                        };
+
+                        plot_func(&state);
+                    } else {
+                        draw_warning("No other slot to compare\0");
+                    }
+                }
+            }
+        }
+        // Composition, the current function applied to the one of the next stored slot
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::NINE) {
+            let inner = (1..FUNCTION_SLOTS)
+                .map(|n| (state.current_slot + n) % FUNCTION_SLOTS)
+                .find_map(|slot| state.func_slots[slot].as_ref());
+            match inner.map(|inner| Function::composition(&state.func_body, inner)) {
+                Some(Some(func_body)) => {
+                    state.func = FastFunction::from(func_body.clone());
+                    state.func_body = func_body;
+
+                    plot_func(&state);
+                }
+                Some(None) => draw_warning("Composition is too long\0"),
+                None => draw_warning("No other slot to compose with\0"),
+            }
+        }
+        // Status bar
+        else if keyboard_state.key_down(key::ALPHA) && debouncer.just_pressed(key::LN) {
+            state.show_status = !state.show_status;
+            if state.show_status {
+                draw_status_bar(&state);
+            } else {
+                clear_status_bar(&state);
+            }
+        }
+        // Debug timing, before the help which shares its keys
+        else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::ZERO)
+        {
+            if debouncer.just_pressed(key::ZERO) {
+                state.debug_timing = !state.debug_timing;
+
+                plot_func(&state);
+            }
+        }
+        // Help
+        else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::ZERO) {
+            help::show_help(&state);
+        }
+        // Animation
+        else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::EXP) {
+            wait_till_released(key::EXP);
+
+            for frame in 0..=ANIMATION_FRAMES {
+                state.parameter = state
+                    .c_start
+                    .lerp(state.c_end, frame as f32 / ANIMATION_FRAMES as f32);
+
+                // Any key stops on the current frame
+                if !plot_func_progressive(&state) || keyboard::scan().any_down() {
+                    refine = true;
+                    while keyboard::scan().any_down() {}
+                    break;
+                }
+            }
+        }
+        // Parameter
+        else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::LOG) {
+            parameter::edit_parameter(&mut state);
+        }
+        // Round bounds, before the go to sharing its keys
+        else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::SINE)
+        {
+            if debouncer.just_pressed(key::SINE) {
+                let area = state.area.snap_to_grid(state.area.grid_step());
+                if area.is_valid() && area != state.area {
+                    state.navigate(area);
+
+                    plot_func(&state);
+                }
+            }
+        }
         // Go to
         else if keyboard_state.key_down(key::ALPHA) && keyboard_state.key_down(key::SINE) {
             goto::goto(&mut state);
@@ -147,9 +625,37 @@ pub fn main() {
         else if keyboard_state.key_down(key::VAR) {
             values::values(&mut state);
         }
+        // User constants, before the editor
+        else if keyboard_state.key_down(key::SHIFT)
+            && keyboard_state.key_down(key::ALPHA)
+            && keyboard_state.key_down(key::TOOLBOX)
+        {
+            constants::edit_constants(&mut state);
+        }
         //Editor
         else if keyboard_state.key_down(key::TOOLBOX) {
             editor::editor(&mut state);
         }
+        // Bookmarks
+        else if let Some(slot) = bookmark_slot.filter(|_| keyboard_state.key_down(key::SHIFT)) {
+            state.bookmarks[slot] = Some(state.area);
+
+            draw_bookmark_indicator(&state);
+        } else if let Some(slot) = bookmark_slot.filter(|_| !keyboard_state.key_down(key::ALPHA)) {
+            if let Some(area) = state.bookmarks[slot].filter(|area| *area != state.area) {
+                plot_transition(&state, &state.area, &area);
+                state.navigate(area);
+
+                plot_func(&state);
+            }
+        }
+
+        if refine && !keyboard::scan().any_down() {
+            plot_func(&state);
+            refine = false;
+        }
+
+        display::wait_for_vblank();
+        timing::msleep(50);
     }
 }
@@ -0,0 +1,49 @@
+use core::fmt::Write;
+
+use heapless::String;
+
+use crate::eadk::{
+    display::{self, SCREEN_WIDTH},
+    key, keyboard, timing, Color, Point, Rect,
+};
+
+use crate::plot::{plot_func, plot_rect};
+use crate::utils::CHARACTER_HEIGHT;
+use crate::utils::{keyboard_complex, wait_till_released};
+
+use crate::State;
+
+// Type a new value for the C variable, the function is plotted again once it's entered
+pub fn edit_parameter(state: &mut State) {
+    let mut input: String<40> = String::new();
+    let header = Rect {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: CHARACTER_HEIGHT * 2,
+    };
+
+    wait_till_released(key::LOG);
+
+    loop {
+        display::push_rect_uniform(header, Color::WHITE);
+
+        let mut s: String<96> = String::new();
+        write!(&mut s, "c = {}\nnew c = {}\0", state.parameter, input).unwrap();
+        display::draw_string(&s, Point::ZERO, false, Color::BLACK, Color::WHITE);
+
+        if keyboard::scan().key_down(key::BACK) {
+            wait_till_released(key::BACK);
+            plot_rect(state, header, 0, 1, 0);
+            return;
+        } else if let Some(c) = keyboard_complex(&mut input) {
+            state.parameter = c;
+            break;
+        }
+
+        timing::msleep(100);
+        display::wait_for_vblank();
+    }
+
+    plot_func(state);
+}
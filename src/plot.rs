@@ -1,19 +1,120 @@
-use libm::{fabsf, floorf, log2f, tanhf, truncf};
+use core::fmt::Write;
+
+use core::f32::consts::PI;
+use heapless::String;
+
+use libm::{fabsf, floorf, fmodf, log2f, roundf, tanhf, truncf};
 
 use crate::eadk::display::{self, SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::eadk::{Color, Rect};
+use crate::eadk::{keyboard, timing, Color, Point, Rect, State as KeyboardState};
+
+use crate::complex::{Complex, ComplexRect};
+
+use crate::function::{FastFunction, Function, MathInstruction};
 
-use crate::complex::Complex;
+use crate::fractal::{
+    escape_time_color, julia_iterate, mandelbrot_iterate, newton_color, newton_iterate,
+};
 
-use crate::function::Evaluate;
+use crate::utils::{map_to_complex, CHARACTER_HEIGHT, CHARACTER_WIDTH};
 
 use crate::State;
 
-pub fn plot_rect(state: &State, rect: Rect) {
-    let color_mapper = state.color_mode.mapper();
+const PREVIEW_SCALE: u16 = 2;
+const COARSE_SCALE: u8 = 4;
+// Rows drawn between two keyboard checks of an interruptible render
+const INTERRUPT_CHECK_ROWS: u16 = 8;
+const TRANSITION_STEPS: u16 = 6;
+// Renders averaged by the debug timing display
+const RENDER_TIMES: usize = 4;
+const STATUS_LINES: u16 = 2;
+const PHASE_LINE_COLOR: Color = Color::BLACK;
+const CONTOUR_COLOR: Color = Color::WHITE;
+// Light enough for dark areas, dark enough for bright ones
+const AXES_COLOR: Color = Color::from_rgb888(180, 180, 180);
+const PROGRESS_COLOR: Color = Color::WHITE;
+// Rows drawn between two updates of the progress bar
+const PROGRESS_ROWS: usize = 4;
+const CONTOUR_LEVELS: usize = 8;
+// Spacing of the samples looking for the range of |f(z)|
+const CONTOUR_SAMPLE_STEP: usize = 8;
+// Spacing of the samples looking for poles and zeros
+const POLE_ZERO_STEP: u16 = 4;
+// Bounds on |f(z)| for a pole and a zero, in widths of a pixel
+const POLE_FACTOR: f32 = 100.;
+const ZERO_FACTOR: f32 = 4.;
+const POLE_COLOR: Color = Color::from_rgb888(255, 0, 255);
+const ZERO_COLOR: Color = Color::from_rgb888(0, 255, 255);
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum RenderMode {
+    DomainColoring,
+    Mandelbrot { max_iter: u32 },
+    Julia { c: Complex, max_iter: u32 },
+    Newton { max_iter: u32, eps: f32 },
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ViewMode {
+    Single,
+    // The left half shows the function of slot_a and the right half the one of slot_b
+    SplitScreen { slot_a: usize, slot_b: usize },
+}
 
+pub(crate) fn pixel_color(state: &State, func: &FastFunction, z: Complex) -> Color {
+    match state.render_mode {
+        RenderMode::DomainColoring => state.color.color(state.eval_with(func, z)),
+        RenderMode::Mandelbrot { max_iter } => escape_time_color(
+            mandelbrot_iterate(z, max_iter),
+            max_iter,
+            state.color.saturation,
+        ),
+        RenderMode::Julia { c, max_iter } => escape_time_color(
+            julia_iterate(z, c, max_iter),
+            max_iter,
+            state.color.saturation,
+        ),
+        RenderMode::Newton { max_iter, eps } => {
+            let (root, n) = newton_iterate(|z| state.eval_with(func, z), z, max_iter, eps);
+            newton_color(root, n, max_iter, state.color.saturation)
+        }
+    }
+}
+
+// Only draw every row_stride-th row, starting pass rows down
+// Every check_keys_every rows, stop and return false if the keyboard changed, 0 never checks
+// Full height renders show their progress over the bottom row when enabled, counting every pass
+pub fn plot_rect(
+    state: &State,
+    rect: Rect,
+    pass: u8,
+    row_stride: u16,
+    check_keys_every: u16,
+) -> bool {
+    let initial = keyboard::scan();
+    // Screen column and row of the axes, drawn along with the rows they cross
+    let axes = state.show_axes.then(|| {
+        let (x, y) = state
+            .area
+            .to_screen(Complex::ZERO, SCREEN_WIDTH, SCREEN_HEIGHT);
+        (roundf(x) as i32, roundf(y) as i32)
+    });
+    let show_progress = state.show_progress && rect.height == SCREEN_HEIGHT;
+    let bottom = rect.y + rect.height - 1;
+    let mut last_drawn = None;
     let mut row: [Color; SCREEN_WIDTH as usize] = [Color::BLACK; SCREEN_WIDTH as usize];
-    (rect.y..rect.height).for_each(|y| {
+    for (n, y) in (rect.y + pass as u16..rect.y + rect.height)
+        .step_by(row_stride as usize)
+        .enumerate()
+    {
+        if check_keys_every != 0
+            && n > 0
+            && n % check_keys_every as usize == 0
+            && interrupted(initial)
+        {
+            return false;
+        }
+
         let imag = (1. - y as f32 / SCREEN_HEIGHT as f32)
             * (state.area.to_imag - state.area.from_imag)
             + state.area.from_imag;
@@ -21,14 +122,25 @@ pub fn plot_rect(state: &State, rect: Rect) {
         (&mut row[0..rect.width as usize])
             .iter_mut()
             .enumerate()
-            .for_each(move |(x, p)| {
-                *p = color_mapper(state.func.eval(Complex {
-                    real: (x as f32 / SCREEN_WIDTH as f32)
-                        * (state.area.to_real - state.area.from_real)
-                        + state.area.from_real,
-                    imag,
-                }));
+            .for_each(|(x, p)| {
+                *p = pixel_color(
+                    state,
+                    &state.func,
+                    Complex {
+                        real: ((rect.x as usize + x) as f32 / SCREEN_WIDTH as f32)
+                            * (state.area.to_real - state.area.from_real)
+                            + state.area.from_real,
+                        imag,
+                    },
+                );
             });
+        if let Some((axis_x, axis_y)) = axes {
+            if axis_y == y as i32 {
+                row[0..rect.width as usize].fill(AXES_COLOR);
+            } else if (rect.x as i32..(rect.x + rect.width) as i32).contains(&axis_x) {
+                row[(axis_x - rect.x as i32) as usize] = AXES_COLOR;
+            }
+        }
         display::push_rect(
             Rect {
                 x: rect.x,
@@ -38,51 +150,844 @@ pub fn plot_rect(state: &State, rect: Rect) {
             },
             &row,
         );
-    });
+        last_drawn = Some(y);
+
+        // Drawn over rows still to come, never over the bottom one just drawn
+        if show_progress && n % PROGRESS_ROWS == 0 && y != bottom {
+            let progress = ((y - rect.y) as f32 + pass as f32 * rect.height as f32)
+                / (row_stride as f32 * rect.height as f32);
+            display::push_rect_uniform(
+                Rect {
+                    x: rect.x,
+                    y: bottom,
+                    width: (rect.width as f32 * progress) as u16,
+                    height: 1,
+                },
+                PROGRESS_COLOR,
+            );
+        }
+    }
+
+    // The bar covers the bottom row unless it was the last one drawn
+    if show_progress && last_drawn != Some(bottom) {
+        plot_rect(
+            state,
+            Rect {
+                y: bottom,
+                height: 1,
+                ..rect
+            },
+            0,
+            1,
+            0,
+        );
+    }
+    true
 }
 
-pub fn plot_func(state: &State) {
-    plot_rect(
+// Draw the whole of area at a reduced resolution inside rect
+pub fn plot_preview(state: &State, area: &ComplexRect, rect: Rect) {
+    plot_blocks(state, &state.func, area, rect, PREVIEW_SCALE, None);
+}
+
+// Draw the screen with scale × scale blocks, stopping early if the keyboard changes
+pub fn plot_func_coarse(state: &State, scale: u8) -> bool {
+    if let ViewMode::SplitScreen { slot_a, slot_b } = state.view_mode {
+        return plot_split(state, slot_a, slot_b, scale as u16, Some(keyboard::scan()));
+    }
+
+    plot_blocks(
         state,
+        &state.func,
+        &state.area,
         Rect {
             x: 0,
             y: 0,
             width: SCREEN_WIDTH,
             height: SCREEN_HEIGHT,
         },
+        scale as u16,
+        Some(keyboard::scan()),
+    )
+}
+
+// Coarse render first for quick feedback, returns false when a key stopped it early
+pub fn plot_func_progressive(state: &State) -> bool {
+    plot_func_coarse(state, COARSE_SCALE) && plot_func_interlaced(state, true)
+}
+
+// Some key is down and the keyboard changed since initial
+fn interrupted(initial: KeyboardState) -> bool {
+    let keyboard_state = keyboard::scan();
+    keyboard_state.any_down() && keyboard_state != initial
+}
+
+fn plot_blocks(
+    state: &State,
+    func: &FastFunction,
+    area: &ComplexRect,
+    rect: Rect,
+    scale: u16,
+    interrupt_from: Option<KeyboardState>,
+) -> bool {
+    for y in (0..rect.height).step_by(scale as usize) {
+        if interrupt_from.is_some_and(interrupted) {
+            return false;
+        }
+
+        let imag =
+            (1. - y as f32 / rect.height as f32) * (area.to_imag - area.from_imag) + area.from_imag;
+
+        (0..rect.width).step_by(scale as usize).for_each(|x| {
+            display::push_rect_uniform(
+                Rect {
+                    x: rect.x + x,
+                    y: rect.y + y,
+                    width: scale.min(rect.width - x),
+                    height: scale.min(rect.height - y),
+                },
+                pixel_color(
+                    state,
+                    func,
+                    Complex {
+                        real: (x as f32 / rect.width as f32) * (area.to_real - area.from_real)
+                            + area.from_real,
+                        imag,
+                    },
+                ),
+            );
+        });
+    }
+    true
+}
+
+// Each half keeps the scale of the full view, around its center, with a white line between them
+fn plot_split(
+    state: &State,
+    slot_a: usize,
+    slot_b: usize,
+    scale: u16,
+    interrupt_from: Option<KeyboardState>,
+) -> bool {
+    let half_area = ComplexRect::from_center(
+        state.area.center(),
+        state.area.width() / 4.,
+        state.area.height() / 2.,
     );
+    let half_width = SCREEN_WIDTH / 2;
+
+    for (n, &slot) in [slot_a, slot_b].iter().enumerate() {
+        // The current slot may have been edited since it was stored
+        let func_body = if slot == state.current_slot {
+            state.func_body.clone()
+        } else {
+            state.func_slots[slot]
+                .clone()
+                .unwrap_or_else(Function::identity)
+        };
+        let rect = Rect {
+            x: n as u16 * half_width,
+            y: 0,
+            width: half_width,
+            height: SCREEN_HEIGHT,
+        };
+        if !plot_blocks(
+            state,
+            &FastFunction::from(func_body),
+            &half_area,
+            rect,
+            scale,
+            interrupt_from,
+        ) {
+            return false;
+        }
+    }
+
+    display::push_rect_uniform(
+        Rect {
+            x: half_width,
+            y: 0,
+            width: 1,
+            height: SCREEN_HEIGHT,
+        },
+        Color::WHITE,
+    );
+    true
+}
+
+// Coarse frames sliding from one area to another, the target still needs a full render
+pub fn plot_transition(state: &State, from: &ComplexRect, to: &ComplexRect) {
+    let screen = Rect {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+    };
+
+    for area in from.viewport_lerp_sequence(to, TRANSITION_STEPS) {
+        plot_blocks(state, &state.func, &area, screen, COARSE_SCALE as u16, None);
+    }
+}
+
+// Reuse what is still on screen after moving from previous, only the uncovered strips are computed
+// Falls back to plot_func_progressive, with its result, when the screen can't be shifted
+pub fn plot_pan(state: &State, previous: &ComplexRect) -> bool {
+    let width = state.area.to_real - state.area.from_real;
+    let height = state.area.to_imag - state.area.from_imag;
+    // Pixels the content moves right and down
+    let dx = (previous.from_real - state.area.from_real) / width * SCREEN_WIDTH as f32;
+    let dy = (state.area.from_imag - previous.from_imag) / height * SCREEN_HEIGHT as f32;
+
+    // Up to the rounding of the pan itself
+    let same_size = fabsf(previous.to_real - previous.from_real - width) < width * 1e-4
+        && fabsf(previous.to_imag - previous.from_imag - height) < height * 1e-4;
+    let whole_pixels = fabsf(dx - roundf(dx)) < 1e-2 && fabsf(dy - roundf(dy)) < 1e-2;
+    let (dx, dy) = (roundf(dx) as i32, roundf(dy) as i32);
+
+    // Phase lines and contours are drawn from the whole screen at once
+    if !same_size
+        || !whole_pixels
+        || dx.unsigned_abs() >= SCREEN_WIDTH as u32
+        || dy.unsigned_abs() >= SCREEN_HEIGHT as u32
+        || state.phase_lines.is_some()
+        || state.show_contours
+        || state.show_poles_zeros
+        || state.view_mode != ViewMode::Single
+    {
+        return plot_func_progressive(state);
+    }
+
+    let mut row: [Color; SCREEN_WIDTH as usize] = [Color::BLACK; SCREEN_WIDTH as usize];
+    let full_row = |y: i32| Rect {
+        x: 0,
+        y: y as u16,
+        width: SCREEN_WIDTH,
+        height: 1,
+    };
+    let mut shift_row = |y: i32| {
+        display::pull_rect(full_row(y - dy), &mut row);
+        if dx > 0 {
+            row.copy_within(..(SCREEN_WIDTH as i32 - dx) as usize, dx as usize);
+        } else {
+            row.copy_within((-dx) as usize.., 0);
+        }
+        display::push_rect(full_row(y), &row);
+    };
+    // Rows are copied in the order that doesn't overwrite the ones still to be read
+    if dy > 0 {
+        (dy..SCREEN_HEIGHT as i32).rev().for_each(&mut shift_row);
+    } else {
+        (0..SCREEN_HEIGHT as i32 + dy).for_each(&mut shift_row);
+    }
+
+    // Uncovered columns then rows
+    if dx != 0 {
+        plot_rect(
+            state,
+            Rect {
+                x: if dx > 0 {
+                    0
+                } else {
+                    SCREEN_WIDTH - (-dx) as u16
+                },
+                y: 0,
+                width: dx.unsigned_abs() as u16,
+                height: SCREEN_HEIGHT,
+            },
+            0,
+            1,
+            0,
+        );
+    }
+    if dy != 0 {
+        plot_rect(
+            state,
+            Rect {
+                x: 0,
+                y: if dy > 0 {
+                    0
+                } else {
+                    SCREEN_HEIGHT - (-dy) as u16
+                },
+                width: SCREEN_WIDTH,
+                height: dy.unsigned_abs() as u16,
+            },
+            0,
+            1,
+            0,
+        );
+    }
+
+    // Overlays were moved along with the rest, plot again where they ended up
+    let redraw_moved = |overlay: Rect| {
+        let (x, y) = (overlay.x as i32 + dx, overlay.y as i32 + dy);
+        let (from_x, to_x) = (
+            x.max(0),
+            (x + overlay.width as i32).min(SCREEN_WIDTH as i32),
+        );
+        let (from_y, to_y) = (
+            y.max(0),
+            (y + overlay.height as i32).min(SCREEN_HEIGHT as i32),
+        );
+        if from_x < to_x && from_y < to_y {
+            plot_rect(
+                state,
+                Rect {
+                    x: from_x as u16,
+                    y: from_y as u16,
+                    width: (to_x - from_x) as u16,
+                    height: (to_y - from_y) as u16,
+                },
+                0,
+                1,
+                0,
+            );
+        }
+    };
+    let line = |lines_up: u16| Rect {
+        x: 0,
+        y: SCREEN_HEIGHT - lines_up * CHARACTER_HEIGHT,
+        width: SCREEN_WIDTH,
+        height: CHARACTER_HEIGHT,
+    };
+    if state.bookmarks.contains(&Some(*previous)) {
+        redraw_moved(Rect {
+            x: SCREEN_WIDTH - 6,
+            y: 2,
+            width: 4,
+            height: 4,
+        });
+    }
+    if state.show_status {
+        redraw_moved(Rect {
+            height: STATUS_LINES * CHARACTER_HEIGHT,
+            ..line(STATUS_LINES)
+        });
+    }
+    if state.debug_timing {
+        redraw_moved(line(if state.show_status {
+            STATUS_LINES + 1
+        } else {
+            1
+        }));
+    }
+    // Whether a warning is shown isn't kept, its line is plotted again anyway
+    redraw_moved(Rect { y: 0, ..line(1) });
+
+    draw_bookmark_indicator(state);
+    if state.show_status {
+        clear_status_bar(state);
+        draw_status_bar(state);
+    }
+    true
+}
+
+pub fn plot_func(state: &State) {
+    let start = timing::millis();
+    plot_func_interlaced(state, false);
+
+    if state.debug_timing {
+        let mut render_times = state.render_times.get();
+        render_times.push(timing::millis() - start);
+        state.render_times.set(render_times);
+        draw_render_times(state);
+    }
+}
+
+// Draw even rows then odd rows, if interruptible a key pressed meanwhile stops the render
+pub fn plot_func_interlaced(state: &State, interruptible: bool) -> bool {
+    let screen = Rect {
+        x: 0,
+        y: 0,
+        width: SCREEN_WIDTH,
+        height: SCREEN_HEIGHT,
+    };
+    let initial = keyboard::scan();
+
+    let check_keys_every = if interruptible {
+        INTERRUPT_CHECK_ROWS
+    } else {
+        0
+    };
+    // Phase lines and contours are computed for the whole screen, split screen leaves them out
+    let finished = if let ViewMode::SplitScreen { slot_a, slot_b } = state.view_mode {
+        plot_split(state, slot_a, slot_b, 1, interruptible.then_some(initial))
+    } else {
+        let finished = plot_rect(state, screen, 0, 2, check_keys_every)
+            && !(interruptible && interrupted(initial))
+            && plot_rect(state, screen, 1, 2, check_keys_every);
+
+        if finished {
+            if let Some(n_lines) = state.phase_lines {
+                draw_phase_lines(state, n_lines);
+            }
+            if state.show_contours {
+                draw_contours(state);
+            }
+            if state.show_poles_zeros {
+                draw_poles_zeros(state);
+            }
+        }
+        finished
+    };
+
+    draw_bookmark_indicator(state);
+    if state.show_status {
+        draw_status_bar(state);
+    }
+    finished
+}
+
+// Darken pixels where arg f(z) is close to a multiple of 2π / n_lines
+pub fn draw_phase_lines(state: &State, n_lines: u8) {
+    if state.render_mode != RenderMode::DomainColoring || n_lines == 0 {
+        return;
+    }
+
+    let n = n_lines as f32;
+    // Phase in units of line spacing, wrapped in [0, n)
+    let phase = |x: u16, y: u16| {
+        let z = Complex {
+            real: (x as f32 / SCREEN_WIDTH as f32) * (state.area.to_real - state.area.from_real)
+                + state.area.from_real,
+            imag: (1. - y as f32 / SCREEN_HEIGHT as f32)
+                * (state.area.to_imag - state.area.from_imag)
+                + state.area.from_imag,
+        };
+        let t = fmodf(state.eval(z).argument() / (2. * PI / n), n);
+        if t < 0. {
+            t + n
+        } else {
+            t
+        }
+    };
+    // Phase difference between neighbors, going the short way around
+    let delta = |a: f32, b: f32| {
+        let d = fabsf(a - b);
+        d.min(n - d)
+    };
+
+    let mut previous_row = [0.; SCREEN_WIDTH as usize];
+    (0..SCREEN_WIDTH).for_each(|x| previous_row[x as usize] = phase(x, 0));
+
+    for y in 1..SCREEN_HEIGHT {
+        let mut left = phase(0, y);
+        for x in 0..SCREEN_WIDTH {
+            let t = if x == 0 { left } else { phase(x, y) };
+            // Half the local slope, so lines stay about one pixel thick
+            let threshold = delta(t, left).max(delta(t, previous_row[x as usize])) / 2.;
+            let frac = t - floorf(t);
+
+            if frac.min(1. - frac) < threshold {
+                display::push_rect_uniform(
+                    Rect {
+                        x,
+                        y,
+                        width: 1,
+                        height: 1,
+                    },
+                    PHASE_LINE_COLOR,
+                );
+            }
+
+            previous_row[x as usize] = t;
+            left = t;
+        }
+    }
+}
+
+// Contours evenly spaced between the smallest and largest |f(z)| on screen
+pub fn draw_contours(state: &State) {
+    if state.render_mode != RenderMode::DomainColoring {
+        return;
+    }
+
+    // Poles and overflows would stretch the range to infinity
+    let (min, max) = (0..SCREEN_HEIGHT)
+        .step_by(CONTOUR_SAMPLE_STEP)
+        .flat_map(|y| {
+            (0..SCREEN_WIDTH)
+                .step_by(CONTOUR_SAMPLE_STEP)
+                .map(move |x| (x, y))
+        })
+        .map(|pos| state.eval(map_to_complex(&state.area, pos)).modulus())
+        .filter(|modulus| modulus.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), modulus| {
+            (min.min(modulus), max.max(modulus))
+        });
+    if min >= max {
+        return;
+    }
+
+    let mut levels = [0.; CONTOUR_LEVELS];
+    levels.iter_mut().enumerate().for_each(|(i, level)| {
+        *level = min + (max - min) * (i + 1) as f32 / (CONTOUR_LEVELS + 1) as f32;
+    });
+    draw_modulus_contours(state, &levels);
+}
+
+// Mark the samples where |f(z)| is a local maximum above a bound with a magenta +
+// and the ones where it's a local minimum under another with a cyan +
+pub fn draw_poles_zeros(state: &State) {
+    if state.render_mode != RenderMode::DomainColoring {
+        return;
+    }
+
+    const COLUMNS: usize = (SCREEN_WIDTH / POLE_ZERO_STEP) as usize;
+    let grid_scale = state.area.width() / SCREEN_WIDTH as f32;
+    // Divisions by zero give NaN right on the pole
+    let sample_row = |y: u16| {
+        let mut row = [0.; COLUMNS];
+        row.iter_mut().enumerate().for_each(|(i, modulus)| {
+            let z = map_to_complex(&state.area, (i as u16 * POLE_ZERO_STEP, y));
+            let w = state.eval(z);
+            *modulus = if w.is_nan() {
+                f32::INFINITY
+            } else {
+                w.modulus()
+            };
+        });
+        row
+    };
+
+    let (mut above, mut row) = (sample_row(0), sample_row(POLE_ZERO_STEP));
+    for y in (2 * POLE_ZERO_STEP..SCREEN_HEIGHT).step_by(POLE_ZERO_STEP as usize) {
+        let below = sample_row(y);
+
+        for i in 1..COLUMNS - 1 {
+            let modulus = row[i];
+            let neighbors = [
+                above[i - 1],
+                above[i],
+                above[i + 1],
+                row[i - 1],
+                row[i + 1],
+                below[i - 1],
+                below[i],
+                below[i + 1],
+            ];
+            // Flat areas aren't extrema
+            let marker = if modulus > POLE_FACTOR * grid_scale
+                && neighbors.iter().all(|n| modulus >= *n)
+                && neighbors.iter().any(|n| modulus > *n)
+            {
+                Some(POLE_COLOR)
+            } else if modulus < ZERO_FACTOR * grid_scale
+                && neighbors.iter().all(|n| modulus <= *n)
+                && neighbors.iter().any(|n| modulus < *n)
+            {
+                Some(ZERO_COLOR)
+            } else {
+                None
+            };
+
+            if let Some(color) = marker {
+                draw_cross(i as u16 * POLE_ZERO_STEP, y - POLE_ZERO_STEP, color);
+            }
+        }
+
+        above = row;
+        row = below;
+    }
+}
+
+// 5 pixels wide, centered on (x, y)
+fn draw_cross(x: u16, y: u16, color: Color) {
+    display::push_rect_uniform(
+        Rect {
+            x: x - 2,
+            y,
+            width: 5,
+            height: 1,
+        },
+        color,
+    );
+    display::push_rect_uniform(
+        Rect {
+            x,
+            y: y - 2,
+            width: 1,
+            height: 5,
+        },
+        color,
+    );
+}
+
+// Draw pixels where |f(z)| crosses one of the increasing levels from a neighbor
+pub fn draw_modulus_contours(state: &State, levels: &[f32]) {
+    // Number of levels under |f(z)|, a contour is where it changes
+    let band = |x: u16, y: u16| {
+        let modulus = state.eval(map_to_complex(&state.area, (x, y))).modulus();
+        levels.partition_point(|level| *level < modulus)
+    };
+
+    let mut previous_row = [0; SCREEN_WIDTH as usize];
+    (0..SCREEN_WIDTH).for_each(|x| previous_row[x as usize] = band(x, 0));
+
+    for y in 1..SCREEN_HEIGHT {
+        let mut left = band(0, y);
+        for x in 0..SCREEN_WIDTH {
+            let b = if x == 0 { left } else { band(x, y) };
+
+            if b != left || b != previous_row[x as usize] {
+                display::push_rect_uniform(
+                    Rect {
+                        x,
+                        y,
+                        width: 1,
+                        height: 1,
+                    },
+                    CONTOUR_COLOR,
+                );
+            }
+
+            previous_row[x as usize] = b;
+            left = b;
+        }
+    }
+}
+
+// Show the bounds of the current area in the bottom right corner
+// At most 8 characters whatever the value, exponents once two decimals aren't enough
+struct StatusNumber(f32);
+
+impl core::fmt::Display for StatusNumber {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let x = self.0;
+        if x == 0. || (0.01..9999.).contains(&fabsf(x)) {
+            write!(f, "{:.2}", x)
+        } else {
+            write!(f, "{:.1e}", x)
+        }
+    }
+}
+
+// The coloring and zoom on the left of the upper line, the bounds on the right of the lower one
+pub fn draw_status_bar(state: &State) {
+    let name = match state.render_mode {
+        RenderMode::DomainColoring => state.color.mapper.name(),
+        RenderMode::Mandelbrot { .. } => "Mandelbrot",
+        RenderMode::Julia { .. } => "Julia",
+        RenderMode::Newton { .. } => "Newton",
+    };
+    let slot_name = |slot: usize| (b'A' + slot as u8) as char;
+    let mut slots: String<8> = String::new();
+    match state.view_mode {
+        ViewMode::Single => write!(&mut slots, "{}", slot_name(state.current_slot)),
+        ViewMode::SplitScreen { slot_a, slot_b } => {
+            write!(&mut slots, "{}|{}", slot_name(slot_a), slot_name(slot_b))
+        }
+    }
+    .unwrap();
+    let mut mode: String<48> = String::new();
+    write!(
+        &mut mode,
+        "[{}] Mode: {}  Zoom: {}x\0",
+        slots,
+        name,
+        StatusNumber(ComplexRect::standard().width() / state.area.width())
+    )
+    .unwrap();
+
+    display::draw_string(
+        &mode,
+        Point::new(0, SCREEN_HEIGHT - STATUS_LINES * CHARACTER_HEIGHT),
+        false,
+        Color::BLACK,
+        Color::WHITE,
+    );
+
+    let mut status: String<64> = String::new();
+    write!(
+        &mut status,
+        "[{}, {}] × [{}, {}]\0",
+        StatusNumber(state.area.from_real),
+        StatusNumber(state.area.to_real),
+        StatusNumber(state.area.from_imag),
+        StatusNumber(state.area.to_imag)
+    )
+    .unwrap();
+
+    display::draw_string(
+        &status,
+        Point::new(
+            SCREEN_WIDTH.saturating_sub((status.chars().count() as u16 - 1) * CHARACTER_WIDTH),
+            SCREEN_HEIGHT - CHARACTER_HEIGHT,
+        ),
+        false,
+        Color::BLACK,
+        Color::WHITE,
+    );
+}
+
+pub fn clear_status_bar(state: &State) {
+    plot_rect(
+        state,
+        Rect {
+            x: 0,
+            y: SCREEN_HEIGHT - STATUS_LINES * CHARACTER_HEIGHT,
+            width: SCREEN_WIDTH,
+            height: STATUS_LINES * CHARACTER_HEIGHT,
+        },
+        0,
+        1,
+        0,
+    );
+}
+
+// Shown in the top left corner until the next render, message must end with \0
+pub fn draw_warning(message: &str) {
+    display::draw_string(message, Point::ZERO, false, Color::RED, Color::WHITE);
+}
+
+// Durations of the last renders in ms, for the debug timing display
+#[derive(Clone, Copy, Default)]
+pub struct RenderTimes {
+    last: [u64; RENDER_TIMES],
+    count: usize,
+}
+
+impl RenderTimes {
+    fn push(&mut self, ms: u64) {
+        self.last[self.count % RENDER_TIMES] = ms;
+        self.count += 1;
+    }
+
+    fn latest(&self) -> u64 {
+        self.last[(self.count + RENDER_TIMES - 1) % RENDER_TIMES]
+    }
+
+    fn average(&self) -> u64 {
+        let n = self.count.min(RENDER_TIMES);
+        self.last[..n].iter().sum::<u64>() / n.max(1) as u64
+    }
+}
+
+// Bottom right, above the status bar when it's shown
+fn draw_render_times(state: &State) {
+    let render_times = state.render_times.get();
+    let mut s: String<48> = String::new();
+    write!(
+        &mut s,
+        "{}ms, avg {}ms",
+        render_times.latest(),
+        render_times.average()
+    )
+    .unwrap();
+    // Its series is the most expensive instruction by far, warn in red
+    let slow = state.func_body.contains(&MathInstruction::Erf);
+    if slow {
+        s.push_str(" (erf)").unwrap();
+    }
+    s.push('\0').unwrap();
+
+    let lines_up = if state.show_status {
+        STATUS_LINES + 1
+    } else {
+        1
+    };
+    display::draw_string(
+        &s,
+        Point::new(
+            SCREEN_WIDTH.saturating_sub((s.chars().count() as u16 - 1) * CHARACTER_WIDTH),
+            SCREEN_HEIGHT - lines_up * CHARACTER_HEIGHT,
+        ),
+        false,
+        if slow { Color::RED } else { Color::BLACK },
+        Color::WHITE,
+    );
+}
+
+// Mark the top right corner when the current area is bookmarked
+pub fn draw_bookmark_indicator(state: &State) {
+    if state.bookmarks.contains(&Some(state.area)) {
+        display::push_rect_uniform(
+            Rect {
+                x: SCREEN_WIDTH - 6,
+                y: 2,
+                width: 4,
+                height: 4,
+            },
+            Color::GREEN,
+        );
+    }
 }
 
 pub mod complex_to_color {
-    use super::{fabsf, floorf, log2f, tanhf, truncf, Color, Complex};
+    use super::{fabsf, floorf, log2f, tanhf, truncf, Color, Complex, PI};
 
-    #[derive(PartialEq, Eq, Clone, Copy)]
+    #[derive(PartialEq, Clone, Copy)]
     pub enum ColorMapper {
         Sigmoid,
         Log2,
         Checkerboard,
+        DiscreteModulus { n_bands: u8 },
+        // Hues at arguments -π and π, in (-π, π]
+        Gradient { hue_a: f32, hue_b: f32 },
+        // Avoids telling red from green
+        Accessible,
     }
     impl ColorMapper {
-        pub fn mapper(self) -> fn(Complex) -> Color {
+        pub fn name(self) -> &'static str {
             match self {
-                ColorMapper::Sigmoid => sigmoid,
-                ColorMapper::Log2 => log2,
-                ColorMapper::Checkerboard => checkerboard,
+                ColorMapper::Sigmoid => "Sigmoid",
+                ColorMapper::Log2 => "Log2",
+                ColorMapper::Checkerboard => "Checkerboard",
+                ColorMapper::DiscreteModulus { .. } => "Bands",
+                ColorMapper::Gradient { .. } => "Gradient",
+                ColorMapper::Accessible => "Accessible",
+            }
+        }
+    }
+
+    pub const MIN_SATURATION: f32 = 0.2;
+    pub const MIN_BANDS: u8 = 2;
+    pub const MAX_BANDS: u8 = 8;
+    // Red to blue through magenta
+    pub const DEFAULT_GRADIENT: ColorMapper = ColorMapper::Gradient {
+        hue_a: 0.,
+        hue_b: -2. * PI / 3.,
+    };
+    pub const HUE_STEP: f32 = PI / 12.;
+    // Blue, orange and reddish purple of the Okabe-Ito palette, evenly spread over the arguments
+    // Mixed in RGB as any hue path between blue and orange goes through green or red
+    const ACCESSIBLE_COLORS: [[f32; 3]; 3] = [[0., 0.45, 0.7], [0.9, 0.6, 0.], [0.8, 0.47, 0.65]];
+
+    #[derive(Clone, Copy)]
+    pub struct ColorSettings {
+        pub mapper: ColorMapper,
+        // Lower is more pastel, in [MIN_SATURATION, 1]
+        pub saturation: f32,
+    }
+    impl ColorSettings {
+        pub fn color(&self, z: Complex) -> Color {
+            match self.mapper {
+                ColorMapper::Sigmoid => sigmoid(z, self.saturation),
+                ColorMapper::Log2 => log2(z, self.saturation),
+                ColorMapper::Checkerboard => checkerboard(z, self.saturation),
+                ColorMapper::DiscreteModulus { n_bands } => {
+                    discrete_modulus(z, n_bands, self.saturation)
+                }
+                ColorMapper::Gradient { hue_a, hue_b } => {
+                    gradient(z, hue_a, hue_b, self.saturation)
+                }
+                ColorMapper::Accessible => accessible(z, self.saturation),
             }
         }
     }
 
-    pub fn log2(z: Complex) -> Color {
+    pub fn log2(z: Complex, saturation: f32) -> Color {
         let value = fabsf(log2f(z.modulus()));
-        Color::from_hv(z.argument(), value - truncf(value))
+        Color::from_hsv(z.argument(), saturation, value - truncf(value))
     }
-    pub fn sigmoid(z: Complex) -> Color {
+    pub fn sigmoid(z: Complex, saturation: f32) -> Color {
         let value = tanhf(z.modulus());
-        Color::from_hv(z.argument(), value)
+        Color::from_hsv(z.argument(), saturation, value)
     }
-    pub fn checkerboard(z: Complex) -> Color {
-        Color::from_hv(
+    pub fn checkerboard(z: Complex, saturation: f32) -> Color {
+        Color::from_hsv(
             z.argument(),
+            saturation,
             if fabsf(floorf(z.real)) as u16 % 2 == fabsf(floorf(z.imag)) as u16 % 2 {
                 0.5
             } else {
@@ -90,4 +995,68 @@ pub mod complex_to_color {
             },
         )
     }
+    // Rings of alternating brightness, n_bands of them across the tanh |z| scale
+    pub fn discrete_modulus(z: Complex, n_bands: u8, saturation: f32) -> Color {
+        let band = floorf(tanhf(z.modulus()) * n_bands as f32) as u8;
+        Color::from_hsv(
+            z.argument(),
+            saturation,
+            if band.is_multiple_of(2) { 1. } else { 0.6 },
+        )
+    }
+    // The hue goes from hue_a to hue_b as the argument goes around, the brightness is the sigmoid one
+    pub fn gradient(z: Complex, hue_a: f32, hue_b: f32, saturation: f32) -> Color {
+        let t = (z.argument() + PI) / (2. * PI);
+        Color::from_hsv(hue_a + (hue_b - hue_a) * t, saturation, tanhf(z.modulus()))
+    }
+
+    // Blend of the two palette colors around the argument, darker toward zeros like sigmoid
+    pub fn accessible(z: Complex, saturation: f32) -> Color {
+        let n = ACCESSIBLE_COLORS.len();
+        let t = (z.argument() + PI) / (2. * PI) * n as f32;
+        let from = ACCESSIBLE_COLORS[floorf(t) as usize % n];
+        let to = ACCESSIBLE_COLORS[(floorf(t) as usize + 1) % n];
+        let value = tanhf(z.modulus());
+        let channel = |i: usize| {
+            let c = from[i] + (to[i] - from[i]) * (t - floorf(t));
+            (value * (1. - saturation * (1. - c)) * 255.) as u8
+        };
+        Color::from_rgb888(channel(0), channel(1), channel(2))
+    }
+
+    // Back into (-π, π] after a step
+    pub fn wrap_hue(hue: f32) -> f32 {
+        if hue > PI {
+            hue - 2. * PI
+        } else if hue <= -PI {
+            hue + 2. * PI
+        } else {
+            hue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_numbers_stay_short() {
+        let values = [
+            0.,
+            -0.5,
+            1234.5678,
+            -9998.999,
+            -9999.999,
+            1e-3,
+            -f32::MIN_POSITIVE,
+            f32::MAX,
+            f32::MIN,
+        ];
+        for &x in values.iter() {
+            let mut s: String<16> = String::new();
+            write!(&mut s, "{}", StatusNumber(x)).unwrap();
+            assert!(s.len() <= 8, "{} is {}", x, s);
+        }
+    }
 }
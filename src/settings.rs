@@ -0,0 +1,109 @@
+use core::convert::TryInto;
+use core::f32::consts::PI;
+
+use crate::plot::complex_to_color::{ColorMapper, MAX_BANDS, MIN_BANDS, MIN_SATURATION};
+use crate::storage::{Codec, StorageSlot};
+
+pub const SETTINGS_SIZE: usize = 16;
+pub const SETTINGS_RECORD: StorageSlot<SETTINGS_SIZE> = StorageSlot::new("complex_settings\0");
+
+// What is kept from one run to the next
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub color_mode: ColorMapper,
+    pub show_axes: bool,
+    pub saturation: f32,
+    pub last_function_slot: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            color_mode: ColorMapper::Sigmoid,
+            show_axes: false,
+            saturation: 1.,
+            last_function_slot: 0,
+        }
+    }
+}
+
+impl Settings {
+    // The mapper, its band count, the axes flag, the slot, the saturation bits then the gradient hues
+    // Unused fields are zeros
+    pub fn to_bytes(self) -> [u8; SETTINGS_SIZE] {
+        let mut bytes = [0; SETTINGS_SIZE];
+        let (mapper, n_bands) = match self.color_mode {
+            ColorMapper::Sigmoid => (0, 0),
+            ColorMapper::Log2 => (1, 0),
+            ColorMapper::Checkerboard => (2, 0),
+            ColorMapper::DiscreteModulus { n_bands } => (3, n_bands),
+            ColorMapper::Gradient { hue_a, hue_b } => {
+                bytes[8..12].copy_from_slice(&hue_a.to_bits().to_le_bytes());
+                bytes[12..16].copy_from_slice(&hue_b.to_bits().to_le_bytes());
+                (4, 0)
+            }
+            ColorMapper::Accessible => (5, 0),
+        };
+        bytes[0] = mapper;
+        bytes[1] = n_bands;
+        bytes[2] = self.show_axes as u8;
+        bytes[3] = self.last_function_slot;
+        bytes[4..8].copy_from_slice(&self.saturation.to_bits().to_le_bytes());
+        bytes
+    }
+
+    // None for anything to_bytes can't have written
+    pub fn from_bytes(b: &[u8]) -> Option<Settings> {
+        let f32_at = |i: usize| -> Option<f32> {
+            Some(f32::from_bits(u32::from_le_bytes(
+                b.get(i..i + 4)?.try_into().ok()?,
+            )))
+        };
+        let is_hue = |hue: f32| hue > -PI && hue <= PI;
+
+        let color_mode = match (b.first()?, b.get(1)?) {
+            (0, _) => ColorMapper::Sigmoid,
+            (1, _) => ColorMapper::Log2,
+            (2, _) => ColorMapper::Checkerboard,
+            (3, n_bands) if (MIN_BANDS..=MAX_BANDS).contains(n_bands) => {
+                ColorMapper::DiscreteModulus { n_bands: *n_bands }
+            }
+            (4, _) => match (f32_at(8)?, f32_at(12)?) {
+                (hue_a, hue_b) if is_hue(hue_a) && is_hue(hue_b) => {
+                    ColorMapper::Gradient { hue_a, hue_b }
+                }
+                _ => return None,
+            },
+            (5, _) => ColorMapper::Accessible,
+            _ => return None,
+        };
+        let show_axes = match b.get(2)? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let saturation = f32_at(4)?;
+        if !(MIN_SATURATION..=1.).contains(&saturation) {
+            return None;
+        }
+
+        Some(Settings {
+            color_mode,
+            show_axes,
+            saturation,
+            last_function_slot: *b.get(3)?,
+        })
+    }
+}
+
+impl Codec for Settings {
+    fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        buf.get_mut(..SETTINGS_SIZE)?
+            .copy_from_slice(&self.to_bytes());
+        Some(SETTINGS_SIZE)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Settings::from_bytes(bytes)
+    }
+}
@@ -0,0 +1,215 @@
+// Named records kept by the calculator between runs
+// Names are null terminated like the strings given to display::draw_string
+
+use core::convert::{TryFrom, TryInto};
+
+use crate::complex::{Complex, ComplexRect};
+use crate::function::{Function, FUNCTION_SIZE};
+
+// Opcodes take a byte, numbers 4 more
+pub const FUNCTION_RECORD_SIZE: usize = FUNCTION_SIZE * 5;
+// A flag then four bounds
+pub const BOOKMARK_SIZE: usize = 17;
+
+pub fn write_record(name: &str, data: &[u8]) -> bool {
+    unsafe { eadk_storage_record_write(name.as_ptr(), data.as_ptr(), data.len()) }
+}
+
+// Size of the record copied into buf, None if it doesn't exist or doesn't fit
+pub fn read_record(name: &str, buf: &mut [u8]) -> Option<usize> {
+    let size = unsafe { eadk_storage_record_read(name.as_ptr(), buf.as_mut_ptr(), buf.len()) };
+    usize::try_from(size).ok().filter(|size| *size <= buf.len())
+}
+
+// Binary form of a value stored in a record
+pub trait Codec: Sized {
+    // Bytes written to buf, None if it's too small
+    fn encode(&self, buf: &mut [u8]) -> Option<usize>;
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+impl<const M: usize> Codec for [u8; M] {
+    fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        buf.get_mut(..M)?.copy_from_slice(self);
+        Some(M)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        bytes.try_into().ok()
+    }
+}
+
+// The real then the imaginary part of each value, as little endian bits
+impl<const M: usize> Codec for [Complex; M] {
+    fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        for (chunk, c) in buf.get_mut(..M * 8)?.chunks_exact_mut(8).zip(self.iter()) {
+            chunk[..4].copy_from_slice(&c.real.to_bits().to_le_bytes());
+            chunk[4..].copy_from_slice(&c.imag.to_bits().to_le_bytes());
+        }
+        Some(M * 8)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != M * 8 {
+            return None;
+        }
+
+        let mut values = [Complex::ZERO; M];
+        for (c, chunk) in values.iter_mut().zip(bytes.chunks_exact(8)) {
+            c.real = f32::from_bits(u32::from_le_bytes(chunk[..4].try_into().ok()?));
+            c.imag = f32::from_bits(u32::from_le_bytes(chunk[4..].try_into().ok()?));
+        }
+        Some(values)
+    }
+}
+
+// Whether the bookmark is set then its bounds as little endian bits, zeros when it isn't
+impl<const M: usize> Codec for [Option<ComplexRect>; M] {
+    fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let chunks = buf
+            .get_mut(..M * BOOKMARK_SIZE)?
+            .chunks_exact_mut(BOOKMARK_SIZE);
+        for (chunk, bookmark) in chunks.zip(self.iter()) {
+            chunk.fill(0);
+            if let Some(rect) = bookmark {
+                chunk[0] = 1;
+                let bounds = [rect.from_real, rect.to_real, rect.from_imag, rect.to_imag];
+                for (bytes, x) in chunk[1..].chunks_exact_mut(4).zip(bounds.iter()) {
+                    bytes.copy_from_slice(&x.to_bits().to_le_bytes());
+                }
+            }
+        }
+        Some(M * BOOKMARK_SIZE)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != M * BOOKMARK_SIZE {
+            return None;
+        }
+
+        let mut bookmarks = [None; M];
+        for (bookmark, chunk) in bookmarks.iter_mut().zip(bytes.chunks_exact(BOOKMARK_SIZE)) {
+            let bound = |i: usize| -> Option<f32> {
+                Some(f32::from_bits(u32::from_le_bytes(
+                    chunk[1 + 4 * i..5 + 4 * i].try_into().ok()?,
+                )))
+            };
+            *bookmark = match chunk[0] {
+                0 => None,
+                1 => Some(ComplexRect {
+                    from_real: bound(0)?,
+                    to_real: bound(1)?,
+                    from_imag: bound(2)?,
+                    to_imag: bound(3)?,
+                })
+                .filter(ComplexRect::is_valid),
+                _ => return None,
+            };
+        }
+        Some(bookmarks)
+    }
+}
+
+impl Codec for Function {
+    fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let bytes = self.to_bytes::<FUNCTION_RECORD_SIZE>()?;
+        buf.get_mut(..bytes.len())?.copy_from_slice(&bytes);
+        Some(bytes.len())
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Function::from_bytes(bytes)
+    }
+}
+
+// A record holding a value of at most N bytes
+pub struct StorageSlot<const N: usize> {
+    name: &'static str,
+}
+
+impl<const N: usize> StorageSlot<N> {
+    pub const fn new(name: &'static str) -> Self {
+        StorageSlot { name }
+    }
+
+    pub fn save<T: Codec>(&self, value: &T) -> bool {
+        let mut buf = [0; N];
+        value
+            .encode(&mut buf)
+            .is_some_and(|len| write_record(self.name, &buf[..len]))
+    }
+
+    pub fn load<T: Codec>(&self) -> Option<T> {
+        let mut buf = [0; N];
+        let len = read_record(self.name, &mut buf)?;
+        T::decode(&buf[..len])
+    }
+}
+
+extern "C" {
+    fn eadk_storage_record_write(name: *const u8, data: *const u8, size: usize) -> bool;
+    fn eadk_storage_record_read(name: *const u8, buffer: *mut u8, capacity: usize) -> isize;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::MathInstruction;
+
+    fn round_trip<T: Codec>(value: &T) -> Option<T> {
+        let mut buf = [0; 80];
+        let len = value.encode(&mut buf)?;
+        T::decode(&buf[..len])
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes: [u8; 8] = [0, 1, 2, 3, 252, 253, 254, 255];
+        assert_eq!(round_trip(&bytes), Some(bytes));
+        assert!(bytes.encode(&mut [0; 4]).is_none());
+        assert!(<[u8; 8]>::decode(&bytes[..7]).is_none());
+    }
+
+    #[test]
+    fn complex_round_trip() {
+        let values = [
+            Complex::ZERO,
+            Complex::I,
+            Complex {
+                real: -1.5,
+                imag: 1e-30,
+            },
+        ];
+        assert_eq!(round_trip(&values), Some(values));
+        assert!(values.encode(&mut [0; 23]).is_none());
+        assert!(<[Complex; 3]>::decode(&[0; 16]).is_none());
+    }
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let bookmarks = [
+            None,
+            Some(ComplexRect::standard()),
+            None,
+            Some(ComplexRect::unit()),
+        ];
+        assert_eq!(round_trip(&bookmarks), Some(bookmarks));
+
+        let mut buf = [0; 4 * BOOKMARK_SIZE];
+        bookmarks.encode(&mut buf).unwrap();
+        buf[0] = 2;
+        assert!(<[Option<ComplexRect>; 4]>::decode(&buf).is_none());
+    }
+
+    #[test]
+    fn function_round_trip() {
+        let func = Function::from(
+            &[
+                MathInstruction::Z,
+                MathInstruction::Number(2.),
+                MathInstruction::Pow,
+            ][..],
+        );
+        assert_eq!(round_trip(&func), Some(func));
+    }
+}
@@ -1,7 +1,9 @@
+use core::f32::consts::PI;
+
 use heapless::String;
 
-use crate::eadk::display::{SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::eadk::{key, keyboard};
+use crate::eadk::display::{self, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::eadk::{key, keyboard, timing, Color, Point, State as KeyboardState};
 
 use crate::complex::{Complex, ComplexRect};
 
@@ -9,6 +11,13 @@ pub const CHARACTERS_BY_LINE: usize = 45;
 pub const CHARACTER_WIDTH: u16 = 7;
 pub const CHARACTER_HEIGHT: u16 = 14;
 
+// In frames of about 50ms
+pub const KEY_REPEAT_DELAY: u16 = 6;
+pub const KEY_REPEAT_INTERVAL: u16 = 1;
+
+// Longer than anyone holds a key on purpose
+pub const RELEASE_TIMEOUT_MS: u32 = 3000;
+
 pub fn map_to_complex(area: &ComplexRect, pos: (u16, u16)) -> Complex {
     Complex {
         real: (pos.0 as f32 / SCREEN_WIDTH as f32) * (area.to_real - area.from_real)
@@ -18,45 +27,275 @@ pub fn map_to_complex(area: &ComplexRect, pos: (u16, u16)) -> Complex {
     }
 }
 
+// Resize the imaginary range around its center so both axes have the same scale on screen
+pub fn enforce_screen_aspect(rect: &mut ComplexRect) {
+    let screen_ratio = SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32;
+    if rect.aspect_ratio() == screen_ratio {
+        return;
+    }
+
+    let shift = rect.width() / screen_ratio / 2.;
+    let mean = rect.center().imag;
+    rect.from_imag = mean - shift;
+    rect.to_imag = mean + shift;
+}
+
 pub fn wait_till_released(k: u32) {
     while keyboard::scan().key_down(k) {}
 }
 
+// false if the key is still down after max_ms, so a stuck key can't hang the app
+pub fn wait_till_released_timeout(k: u32, max_ms: u32) -> bool {
+    let start = timing::millis();
+    while keyboard::scan().key_down(k) {
+        if timing::millis() - start >= max_ms as u64 {
+            return false;
+        }
+    }
+    true
+}
+
+// Turn a held key into presses: one at once, then after a delay one every interval, in frames
+pub struct KeyRepeat {
+    key: u32,
+    frames_held: u16,
+    initial_delay: u16,
+    repeat_interval: u16,
+}
+
+impl KeyRepeat {
+    pub const fn new(key: u32, initial_delay: u16, repeat_interval: u16) -> Self {
+        KeyRepeat {
+            key,
+            frames_held: 0,
+            initial_delay,
+            repeat_interval,
+        }
+    }
+
+    // Must be called once per frame, even when the key isn't used, to notice releases
+    pub fn pressed(&mut self, keyboard_state: KeyboardState) -> bool {
+        if !keyboard_state.key_down(self.key) {
+            self.frames_held = 0;
+            return false;
+        }
+
+        let frames = self.frames_held;
+        self.frames_held = self.frames_held.saturating_add(1);
+
+        frames == 0
+            || (frames >= self.initial_delay
+                && (frames - self.initial_delay).is_multiple_of(self.repeat_interval.max(1)))
+    }
+
+    pub fn frames_held(&self) -> u16 {
+        self.frames_held
+    }
+}
+
+// Turn a key into a single press however long it's held, for toggles and mode changes
+pub struct Debouncer {
+    previous: KeyboardState,
+    current: KeyboardState,
+}
+
+impl Debouncer {
+    pub fn new(keyboard_state: KeyboardState) -> Self {
+        Debouncer {
+            previous: keyboard_state,
+            current: keyboard_state,
+        }
+    }
+
+    // Must be called once per frame with the latest scan
+    pub fn update(&mut self, keyboard_state: KeyboardState) {
+        self.previous = self.current;
+        self.current = keyboard_state;
+    }
+
+    pub fn just_pressed(&self, k: u32) -> bool {
+        self.current.key_down(k) && !self.previous.key_down(k)
+    }
+}
+
+fn pressed_digit(keyboard_state: KeyboardState) -> Option<char> {
+    [
+        (key::ZERO, '0'),
+        (key::ONE, '1'),
+        (key::TWO, '2'),
+        (key::THREE, '3'),
+        (key::FOUR, '4'),
+        (key::FIVE, '5'),
+        (key::SIX, '6'),
+        (key::SEVEN, '7'),
+        (key::EIGHT, '8'),
+        (key::NINE, '9'),
+        (key::DOT, '.'),
+    ]
+    .iter()
+    .find(|(k, _)| keyboard_state.key_down(*k))
+    .map(|(_, c)| *c)
+}
+
 pub fn keyboard_number<const N: usize>(num: &mut String<N>) -> Option<f32> {
     let keyboard_state = keyboard::scan();
 
-    if keyboard_state.key_down(key::ZERO) {
-        num.push('0').unwrap_or(());
-    } else if keyboard_state.key_down(key::ONE) {
-        num.push('1').unwrap_or(());
-    } else if keyboard_state.key_down(key::TWO) {
-        num.push('2').unwrap_or(());
-    } else if keyboard_state.key_down(key::THREE) {
-        num.push('3').unwrap_or(());
-    } else if keyboard_state.key_down(key::FOUR) {
-        num.push('4').unwrap_or(());
-    } else if keyboard_state.key_down(key::FIVE) {
-        num.push('5').unwrap_or(());
-    } else if keyboard_state.key_down(key::SIX) {
-        num.push('6').unwrap_or(());
-    } else if keyboard_state.key_down(key::SEVEN) {
-        num.push('7').unwrap_or(());
-    } else if keyboard_state.key_down(key::EIGHT) {
-        num.push('8').unwrap_or(());
-    } else if keyboard_state.key_down(key::NINE) {
-        num.push('9').unwrap_or(());
-    } else if keyboard_state.key_down(key::DOT) {
-        num.push('.').unwrap_or(());
+    if let Some(digit) = pressed_digit(keyboard_state) {
+        num.push(digit).unwrap_or(());
+    } else if keyboard_state.key_down(key::EE)
+        && !num.contains('e')
+        && num.contains(|c: char| c.is_ascii_digit())
+    {
+        num.push('e').unwrap_or(());
+    } else if keyboard_state.key_down(key::MINUS) {
+        // Once there is an exponent, its sign is the one switched
+        let sign_index = num.find('e').map_or(0, |i| i + 1);
+        match num[sign_index..].chars().next() {
+            Some('-') => {
+                *num = num
+                    .chars()
+                    .take(sign_index)
+                    .chain(num.chars().skip(sign_index + 1))
+                    .collect()
+            }
+            None | Some(_) => {
+                *num = num
+                    .chars()
+                    .take(sign_index)
+                    .chain(['-'])
+                    .chain(num.chars().skip(sign_index))
+                    .collect()
+            }
+        }
+    } else if keyboard_state.key_down(key::BACKSPACE) && num.len() > 0 {
+        num.pop().unwrap();
+    } else if keyboard_state.key_down(key::EXE) {
+        // Incomplete input such as `-` or `1e` is kept for more editing
+        match num.as_str().parse::<f32>() {
+            // A stuck EXE aborts the input rather than returning over and over
+            Ok(x) => {
+                if wait_till_released_timeout(key::EXE, RELEASE_TIMEOUT_MS) {
+                    return Some(x);
+                }
+                num.clear();
+            }
+            Err(_) => {
+                display::draw_string(
+                    "Invalid number\0",
+                    Point::ZERO,
+                    false,
+                    Color::RED,
+                    Color::WHITE,
+                );
+                timing::msleep(400);
+                if !wait_till_released_timeout(key::EXE, RELEASE_TIMEOUT_MS) {
+                    num.clear();
+                }
+            }
+        }
+    }
+    None
+}
+
+// What to show for a number being typed, a cursor while there's nothing yet
+pub fn number_or_cursor(num: &str) -> &str {
+    if num.is_empty() {
+        "_"
+    } else {
+        num
+    }
+}
+
+pub fn keyboard_complex<const N: usize>(num: &mut String<N>) -> Option<Complex> {
+    let keyboard_state = keyboard::scan();
+
+    if let Some(digit) = pressed_digit(keyboard_state) {
+        num.push(digit).unwrap_or(());
+    } else if keyboard_state.key_down(key::PLUS) {
+        num.push('+').unwrap_or(());
+    } else if keyboard_state.key_down(key::MINUS) {
+        num.push('-').unwrap_or(());
+    } else if keyboard_state.key_down(key::IMAGINARY) {
+        num.push('i').unwrap_or(());
+    } else if keyboard_state.key_down(key::EE) {
+        num.push('e').unwrap_or(());
+    } else if keyboard_state.key_down(key::BACKSPACE) && !num.is_empty() {
+        num.pop().unwrap();
+    } else if keyboard_state.key_down(key::EXE) {
+        if let Some(c) = parse_complex(num) {
+            if wait_till_released_timeout(key::EXE, RELEASE_TIMEOUT_MS) {
+                return Some(c);
+            }
+            num.clear();
+        }
+    }
+    None
+}
+
+pub fn keyboard_angle<const N: usize>(num: &mut String<N>) -> Option<f32> {
+    let keyboard_state = keyboard::scan();
+
+    // Nothing can follow a multiple of pi
+    if keyboard_state.key_down(key::PI) && !num.ends_with("pi") {
+        num.push_str("pi").unwrap_or(());
+    } else if let Some(digit) = pressed_digit(keyboard_state).filter(|_| !num.ends_with("pi")) {
+        num.push(digit).unwrap_or(());
     } else if keyboard_state.key_down(key::MINUS) {
         match num.chars().next() {
             Some('-') => *num = num.chars().skip(1).collect(),
             None | Some(_) => *num = num.chars().rev().chain(['-']).rev().collect(),
         }
-    } else if keyboard_state.key_down(key::BACKSPACE) && num.len() > 0 {
+    } else if keyboard_state.key_down(key::BACKSPACE) && num.ends_with("pi") {
+        num.truncate(num.len() - 2);
+    } else if keyboard_state.key_down(key::BACKSPACE) && !num.is_empty() {
         num.pop().unwrap();
-    } else if keyboard_state.key_down(key::EXE) && !num.is_empty() {
-        wait_till_released(key::EXE);
-        return Some(num.as_str().parse::<f32>().unwrap());
+    } else if keyboard_state.key_down(key::EXE) {
+        if let Some(angle) = parse_angle(num) {
+            if wait_till_released_timeout(key::EXE, RELEASE_TIMEOUT_MS) {
+                return Some(angle);
+            }
+            num.clear();
+        }
     }
     None
 }
+
+/// Parse an angle in radians, optionally written as a multiple of pi such as `-0.5pi`
+pub fn parse_angle(s: &str) -> Option<f32> {
+    match s.trim().strip_suffix("pi") {
+        Some("") | Some("+") => Some(PI),
+        Some("-") => Some(-PI),
+        Some(factor) => factor.parse::<f32>().ok().map(|factor| factor * PI),
+        None => s.trim().parse::<f32>().ok(),
+    }
+}
+
+/// Parse a complex number written as `a+bi`, `a-bi`, `a` or `bi`
+pub fn parse_complex(s: &str) -> Option<Complex> {
+    let s = s.trim();
+
+    if let Some(s) = s.strip_suffix('i') {
+        // The imaginary part starts at the last sign which isn't an exponent sign
+        let split = s
+            .char_indices()
+            .rev()
+            .find(|&(i, c)| {
+                (c == '+' || c == '-') && i > 0 && !s[..i].ends_with(['e', 'E'].as_ref())
+            })
+            .map(|(i, _)| i);
+
+        let (real, imag) = match split {
+            Some(i) => (s[..i].parse::<f32>().ok()?, &s[i..]),
+            None => (0., s),
+        };
+        let imag = match imag {
+            "" | "+" => 1.,
+            "-" => -1.,
+            imag => imag.parse::<f32>().ok()?,
+        };
+
+        Some(Complex { real, imag })
+    } else {
+        s.parse::<f32>().ok().map(Complex::from_real)
+    }
+}
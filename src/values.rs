@@ -7,31 +7,52 @@ use crate::eadk::{
     key, keyboard, timing, Color, Point, Rect,
 };
 
-use crate::function::Evaluate;
-
-use crate::plot::plot_rect;
-use crate::utils::map_to_complex;
-use crate::utils::CHARACTER_HEIGHT;
+use crate::plot::{pixel_color, plot_rect, RenderMode};
+use crate::utils::{map_to_complex, wait_till_released, KeyRepeat};
+use crate::utils::{CHARACTER_HEIGHT, KEY_REPEAT_DELAY, KEY_REPEAT_INTERVAL};
 
 use crate::State;
 
 pub fn values(state: &mut State) {
     let (mut x, mut y) = (SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2);
+    let mut show_derivative = false;
+    // EXE sets the start of the animated path of c then its end
+    let mut setting_end = false;
+    // Indexed by key code
+    let mut arrows = [key::LEFT, key::UP, key::DOWN, key::RIGHT]
+        .map(|k| KeyRepeat::new(k, KEY_REPEAT_DELAY, KEY_REPEAT_INTERVAL));
+    // The cursor speeds up the longer an arrow is held
+    let step = |frames: u16| match frames {
+        0..=10 => 1,
+        11..=20 => 3,
+        _ => 10,
+    };
 
     loop {
         let keyboard_state = keyboard::scan();
+        // The cursor must stay below the values display
+        let header_height = CHARACTER_HEIGHT * if show_derivative { 3 } else { 2 };
+        y = y.max(header_height);
 
         let z = map_to_complex(&state.area, (x, y));
-        let fz = state.func.eval(z);
+        let fz = state.eval(z);
 
         let mut s: String<256> = String::new();
-        write!(&mut s, "z = {z}\nf(z) = {fz}\0").unwrap();
+        write!(&mut s, "z = {z}\nf(z) = {fz}").unwrap();
+        if show_derivative {
+            // Symmetric difference, the step scales with the zoom level
+            let h = (state.area.to_real - state.area.from_real) / SCREEN_WIDTH as f32 / 10.;
+            let dfz = (state.eval(z + h) - state.eval(z - h)) / (2. * h);
+            write!(&mut s, "\nf'(z) = {dfz}").unwrap();
+        }
+        s.push('\0').unwrap();
+
         display::push_rect_uniform(
             Rect {
                 x: 0,
                 y: 0,
                 width: SCREEN_WIDTH,
-                height: CHARACTER_HEIGHT * 2,
+                height: header_height,
             },
             Color::WHITE,
         );
@@ -43,33 +64,105 @@ pub fn values(state: &mut State) {
                 width: 1,
                 height: 1,
             },
-            (state.color_mode.mapper())(fz),
+            pixel_color(state, &state.func, z),
         );
 
-        if keyboard_state.key_down(key::RIGHT) {
-            x += 1;
-        } else if keyboard_state.key_down(key::LEFT) {
-            x -= 1;
+        let pressed = arrows.each_mut().map(|arrow| arrow.pressed(keyboard_state));
+        let held = |k: u32| arrows[k as usize].frames_held();
+
+        let previous_position = (x, y);
+
+        if pressed[key::RIGHT as usize] {
+            x = (x + step(held(key::RIGHT))).min(SCREEN_WIDTH - 1);
+        } else if pressed[key::LEFT as usize] {
+            x = x.saturating_sub(step(held(key::LEFT)));
         }
 
-        if keyboard_state.key_down(key::UP) {
-            y -= 1;
-        } else if keyboard_state.key_down(key::DOWN) {
-            y += 1;
+        if pressed[key::UP as usize] {
+            y = y.saturating_sub(step(held(key::UP))).max(header_height);
+        } else if pressed[key::DOWN as usize] {
+            y = (y + step(held(key::DOWN))).min(SCREEN_HEIGHT - 1);
+        } else if keyboard_state.key_down(key::SHIFT) {
+            if show_derivative {
+                plot_rect(
+                    state,
+                    Rect {
+                        x: 0,
+                        y: 0,
+                        width: SCREEN_WIDTH,
+                        height: CHARACTER_HEIGHT * 3,
+                    },
+                    0,
+                    1,
+                    0,
+                );
+            }
+            show_derivative = !show_derivative;
+            wait_till_released(key::SHIFT);
+        } else if keyboard_state.key_down(key::EXE) {
+            let mut message: String<64> = String::new();
+            if setting_end {
+                state.c_end = z;
+                write!(&mut message, "Animation ends at c = {z}\0").unwrap();
+            } else {
+                state.c_start = z;
+                write!(&mut message, "Animation starts at c = {z}\0").unwrap();
+            }
+            setting_end = !setting_end;
+
+            display::push_rect_uniform(
+                Rect {
+                    x: 0,
+                    y: 0,
+                    width: SCREEN_WIDTH,
+                    height: CHARACTER_HEIGHT,
+                },
+                Color::WHITE,
+            );
+            display::draw_string(&message, Point::ZERO, false, Color::BLACK, Color::WHITE);
+            wait_till_released(key::EXE);
+            timing::msleep(500);
         } else if keyboard_state.key_down(key::BACK) {
+            wait_till_released(key::BACK);
             plot_rect(
                 state,
                 Rect {
                     x: 0,
                     y: 0,
                     width: SCREEN_WIDTH,
-                    height: CHARACTER_HEIGHT * 2,
+                    height: CHARACTER_HEIGHT * 3,
                 },
+                0,
+                1,
+                0,
             );
 
             break;
         }
 
+        // In Julia mode the cursor picks the parameter c
+        if let RenderMode::Julia { max_iter, .. } = state.render_mode {
+            if (x, y) != previous_position {
+                state.render_mode = RenderMode::Julia {
+                    c: map_to_complex(&state.area, (x, y)),
+                    max_iter,
+                };
+                plot_rect(
+                    state,
+                    Rect {
+                        x: 0,
+                        y: header_height,
+                        width: SCREEN_WIDTH,
+                        height: SCREEN_HEIGHT - header_height,
+                    },
+                    0,
+                    1,
+                    0,
+                );
+            }
+        }
+
+        // After the Julia render, which would cover it
         display::push_rect_uniform(
             Rect {
                 x,